@@ -0,0 +1,101 @@
+use bevy::prelude::Vec3;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use snake_bevy::bench::{move_on_ground, GroundMesh};
+use std::fmt::Write;
+
+const SIZE: f32 = 2000.0;
+const RADIUS: f32 = 30.0;
+/// Quads along each side of the generated grounds.
+const GRID: [usize; 3] = [8, 32, 128];
+
+fn height(x: f32, z: f32) -> f32 {
+    ((x * 0.01).sin() + (z * 0.013).cos()) * 40.0
+}
+
+/// Hilly `n` x `n` grid as obj data.
+fn grid_obj(n: usize) -> String {
+    let mut s = String::new();
+    let step = SIZE / n as f32;
+    for i in 0..=n {
+        for j in 0..=n {
+            let x = i as f32 * step - SIZE * 0.5;
+            let z = j as f32 * step - SIZE * 0.5;
+            writeln!(s, "v {} {} {}", x, height(x, z), z).unwrap();
+        }
+    }
+    for i in 0..n {
+        for j in 0..n {
+            let a = i * (n + 1) + j + 1;
+            let b = a + n + 1;
+            writeln!(s, "f {} {} {}", a, a + 1, b).unwrap();
+            writeln!(s, "f {} {} {}", b, a + 1, b + 1).unwrap();
+        }
+    }
+    s
+}
+
+/// Points along a circle, standing on the ground.
+fn points(count: usize) -> Vec<Vec3> {
+    (0..count)
+        .map(|i| {
+            let a = i as f32 / count as f32 * std::f32::consts::TAU;
+            let (x, z) = (a.cos() * SIZE * 0.3, a.sin() * SIZE * 0.3);
+            Vec3::new(x, height(x, z) + RADIUS, z)
+        })
+        .collect()
+}
+
+fn bench_ground(c: &mut Criterion) {
+    let meshes: Vec<_> = GRID
+        .iter()
+        .map(|&n| (n * n * 2, GroundMesh::from_obj(&grid_obj(n)).unwrap()))
+        .collect();
+    let points = points(1000);
+
+    let mut group = c.benchmark_group("fix_position");
+    for (triangles, mesh) in meshes.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(triangles), mesh, |b, mesh| {
+            let mut i = 0;
+            b.iter(|| {
+                i = (i + 1) % points.len();
+                mesh.fix_position(black_box(points[i]), 3.0, RADIUS, 0)
+            });
+        });
+    }
+    group.finish();
+
+    // one frame of a body at full speed, 5 units in 2 steps
+    let mut group = c.benchmark_group("move_on_ground");
+    for (triangles, mesh) in meshes.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(triangles), mesh, |b, mesh| {
+            let mut i = 0;
+            b.iter(|| {
+                i = (i + 1) % points.len();
+                let from = points[i];
+                move_on_ground(mesh, from, black_box(from + Vec3::new(5.0, 0.0, 0.0)), 0)
+            });
+        });
+    }
+    group.finish();
+
+    // the per frame cost of the whole simulation on the bundled ground
+    let ground = include_str!("../assets/ground.obj");
+    let mut group = c.benchmark_group("update");
+    for (name, ground) in [("flat", None), ("ground", Some(ground))] {
+        group.bench_function(name, |b| {
+            let mut app = snake_bevy::init(ground);
+            let mut positions = [0.0; 30];
+            let mut frame = 0u32;
+            b.iter(|| {
+                frame += 1;
+                let a = frame as f32 * 0.01;
+                let axis = [a.cos(), a.sin()];
+                snake_bevy::update(&mut app, 1.0 / 60.0, &[0.0; 6], &axis, &mut positions);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ground);
+criterion_main!(benches);
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+
+use super::logic::*;
+
+/// A waypoint is reached this close to the head.
+const REACH: f32 = RADIUS * 2.0;
+/// Radians per second the wander heading drifts at most.
+const WANDER_DRIFT: f32 = 3.0;
+/// Own bodies this close behind the head are never avoided.
+const OWN_NECK: usize = 3;
+
+/// Steers a snake with a `SnakeInput` by weighing a few behaviours: follow the waypoints or
+/// wander without them, seek pickups in sight and avoid or flee from other snakes.
+#[derive(Component, Clone)]
+pub struct SnakeAi {
+    /// Visited in a loop when not empty, otherwise the snake wanders.
+    pub waypoints: Vec<Vec3>,
+    pub wander: f32,
+    pub follow: f32,
+    pub seek: f32,
+    /// Push away from bodies of any snake, the own ones past the neck too.
+    pub avoid: f32,
+    /// Turn away from other heads and sprint.
+    pub flee: f32,
+    /// Pickups farther away than this are ignored.
+    pub sight: f32,
+    /// Bodies closer than this are avoided, heads closer than twice this fled.
+    pub avoid_radius: f32,
+    next_waypoint: usize,
+    wander_heading: f32,
+    rng: u32,
+}
+
+impl SnakeAi {
+    /// `seed` picks the wander of the snake, the same seed wanders the same way.
+    pub fn new(waypoints: Vec<Vec3>, seed: u32) -> Self {
+        Self {
+            waypoints,
+            wander: 0.5,
+            follow: 1.0,
+            seek: 1.0,
+            avoid: 2.0,
+            flee: 2.0,
+            sight: DISTANCE * 5.0,
+            avoid_radius: RADIUS * 3.0,
+            next_waypoint: 0,
+            wander_heading: 0.0,
+            rng: seed.wrapping_mul(0x9e37_79b9) | 1,
+        }
+    }
+
+    /// Uniform in -1..1, xorshift.
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// Input for the snake led by `leader`, `others` are the heads and bodies of every
+    /// other snake.
+    fn steer<'a>(
+        &mut self,
+        leader: &Leader,
+        others: impl Iterator<Item = &'a Leader>,
+        pickups: impl Iterator<Item = Vec3>,
+        delta_time: f32,
+    ) -> SnakeInput {
+        let flat = |v: Vec3| Vec3::new(v.x, 0.0, v.z);
+        let head = leader.snake_head.head_position();
+        let mut steer = Vec3::ZERO;
+        let mut pace = Pace::Normal;
+
+        if self.waypoints.is_empty() {
+            self.wander_heading += self.random() * WANDER_DRIFT * delta_time;
+            steer += Quat::from_rotation_y(self.wander_heading) * Vec3::NEG_Z * self.wander;
+            pace = Pace::Slow;
+        } else {
+            let mut v = flat(self.waypoints[self.next_waypoint] - head);
+            if v.length() < REACH {
+                self.next_waypoint = (self.next_waypoint + 1) % self.waypoints.len();
+                v = flat(self.waypoints[self.next_waypoint] - head);
+            }
+            steer += v.normalize_or_zero() * self.follow;
+        }
+
+        let nearest = pickups
+            .map(|p| flat(p - head))
+            .filter(|v| v.length() < self.sight)
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        if let Some(v) = nearest {
+            steer += v.normalize_or_zero() * self.seek;
+            pace = Pace::Normal;
+        }
+
+        let mut push = |p: Vec3, radius: f32, weight: f32| {
+            let v = flat(head - p);
+            let d = v.length();
+            if d < radius {
+                steer += v.normalize_or_zero() * (1.0 - d / radius) * weight;
+                true
+            } else {
+                false
+            }
+        };
+        let own = leader.snake_head.bodies.iter().skip(OWN_NECK);
+        for body in own {
+            push(body.position, self.avoid_radius, self.avoid);
+        }
+        for other in others {
+            let bodies = &other.snake_head.bodies;
+            if push(bodies[0].position, self.avoid_radius * 2.0, self.flee) {
+                pace = Pace::Sprint;
+            }
+            for body in bodies.iter().skip(1) {
+                push(body.position, self.avoid_radius, self.avoid);
+            }
+        }
+
+        SnakeInput {
+            target: None,
+            direction: steer.normalize_or_zero(),
+            pace,
+        }
+    }
+}
+
+pub fn ai_steer(
+    time: Res<Time>,
+    mut query_ai: Query<(Entity, &mut SnakeAi, &mut SnakeInput)>,
+    query_leader: Query<(Entity, &Leader)>,
+    query_pickup: Query<&Transform, With<Pickup>>,
+) {
+    let delta_time = time.delta_seconds();
+    for (entity, mut ai, mut input) in query_ai.iter_mut() {
+        let Ok((_, leader)) = query_leader.get(entity) else {
+            continue;
+        };
+        let others = query_leader
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other)| other);
+        let pickups = query_pickup.iter().map(|tm| tm.translation);
+        *input = ai.steer(leader, others, pickups, delta_time);
+    }
+}
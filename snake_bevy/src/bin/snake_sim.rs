@@ -0,0 +1,161 @@
+//! Headless simulation of the snake logic, for evaluating movement without a display.
+//!
+//! ```text
+//! snake_sim [--ground ground.obj] [--level level.txt] [--input script.txt] [--frames 600]
+//!           [--dt 0.016667] [--output out.csv|out.json] [--metrics metrics.txt]
+//! ```
+//!
+//! The input script has one command per line, applied from the given frame on:
+//!
+//! ```text
+//! # frame command args
+//! 0 axis 1 0          # axis input x y
+//! 120 target 100 50   # click to move to x z, casting a ray down on the ground
+//! 240 release         # stop clicking
+//! 300 stack up        # stack up or down at the head
+//! 360 formation 1     # 0: stack, 1: circle, 2: line abreast
+//! ```
+//!
+//! The output has the position of every body for every frame, as csv
+//! (`frame,time,body,x,y,z`) or json when the output ends with `.json`.
+//! `--metrics` writes the movement metrics of every body at the end of the run.
+//! `--level` places the snake, portals, pickups and computer controlled snakes from a level
+//! file saved by the editor of the `snake_bevy` binary, the output has the scripted snake only.
+
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+struct Options {
+    ground: Option<String>,
+    level: Option<String>,
+    input: Option<String>,
+    frames: u32,
+    dt: f32,
+    output: Option<String>,
+    metrics: Option<String>,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: snake_sim [--ground file.obj] [--level level.txt] [--input script.txt] [--frames n] \
+         [--dt seconds] [--output file.csv|file.json] [--metrics file.txt]"
+    );
+    process::exit(1);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        ground: None,
+        level: None,
+        input: None,
+        frames: 600,
+        dt: 1.0 / 60.0,
+        output: None,
+        metrics: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--ground" => options.ground = Some(value),
+            "--level" => options.level = Some(value),
+            "--input" => options.input = Some(value),
+            "--frames" => options.frames = value.parse().unwrap_or_else(|_| usage()),
+            "--dt" => options.dt = value.parse().unwrap_or_else(|_| usage()),
+            "--output" => options.output = Some(value),
+            "--metrics" => options.metrics = Some(value),
+            _ => usage(),
+        }
+    }
+    options
+}
+
+fn write_output(out: &mut dyn Write, json: bool, dt: f32, frames: &[Vec<f32>]) -> io::Result<()> {
+    if json {
+        write!(out, "{{\"dt\":{},\"frames\":[", dt)?;
+        for (i, positions) in frames.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "[")?;
+            for (j, p) in positions.chunks(3).enumerate() {
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "[{},{},{}]", p[0], p[1], p[2])?;
+            }
+            write!(out, "]")?;
+        }
+        writeln!(out, "]}}")
+    } else {
+        writeln!(out, "frame,time,body,x,y,z")?;
+        for (i, positions) in frames.iter().enumerate() {
+            for (j, p) in positions.chunks(3).enumerate() {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    i,
+                    (i + 1) as f32 * dt,
+                    j,
+                    p[0],
+                    p[1],
+                    p[2]
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let options = parse_args();
+    let read = |path: &str| {
+        fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("can't read {}: {}", path, e);
+            process::exit(1);
+        })
+    };
+    let ground = options.ground.as_deref().map(read);
+    let script = options.input.as_deref().map(read).unwrap_or_default();
+
+    let mut app = snake_bevy::init(ground.as_deref());
+    if let Some(level) = options.level.as_deref().map(read) {
+        if let Err(e) = snake_bevy::load_level(&mut app, &level) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    if options.metrics.is_some() {
+        snake_bevy::enable_metrics(&mut app);
+    }
+    let mut frames = Vec::with_capacity(options.frames as usize);
+    let result = snake_bevy::run_script(&mut app, &script, options.frames, options.dt, |p| {
+        frames.push(p.to_vec())
+    });
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    if let Some(path) = options.metrics.as_deref() {
+        if let Err(e) = fs::write(path, snake_bevy::metrics_report(&mut app)) {
+            eprintln!("can't write metrics: {}", e);
+            process::exit(1);
+        }
+    }
+
+    let json = options
+        .output
+        .as_deref()
+        .is_some_and(|path| path.ends_with(".json"));
+    let result = match options.output.as_deref() {
+        Some(path) => fs::File::create(path)
+            .map(io::BufWriter::new)
+            .and_then(|mut f| write_output(&mut f, json, options.dt, &frames)),
+        None => write_output(&mut io::stdout().lock(), json, options.dt, &frames),
+    };
+    if let Err(e) = result {
+        eprintln!("can't write output: {}", e);
+        process::exit(1);
+    }
+}
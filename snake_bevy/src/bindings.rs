@@ -0,0 +1,358 @@
+//! Keyboard and gamepad bindings of the snake actions, read from a bindings file with one
+//! action and its keys per line. Gamepad buttons are the `GamepadButtonType` names with a
+//! `Pad` prefix and the left stick walks too, with a radial dead zone:
+//!
+//! ```text
+//! # action keys...
+//! up W Up
+//! stack_up U PadSouth
+//! deadzone 0.2
+//! ```
+//!
+//! Actions missing in the file keep their default keys.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    StackUp,
+    StackDown,
+    Formation,
+    Save,
+    Load,
+    Sprint,
+    Slow,
+}
+
+const ACTIONS: [(&str, Action); 11] = [
+    ("up", Action::Up),
+    ("down", Action::Down),
+    ("left", Action::Left),
+    ("right", Action::Right),
+    ("stack_up", Action::StackUp),
+    ("stack_down", Action::StackDown),
+    ("formation", Action::Formation),
+    ("save", Action::Save),
+    ("load", Action::Load),
+    ("sprint", Action::Sprint),
+    ("slow", Action::Slow),
+];
+
+/// Keys a bindings file can name, by their `KeyCode` names.
+const KEYS: [KeyCode; 60] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Return,
+    KeyCode::Back,
+    KeyCode::Delete,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+];
+
+const BUTTONS: [GamepadButtonType; 19] = [
+    GamepadButtonType::South,
+    GamepadButtonType::East,
+    GamepadButtonType::North,
+    GamepadButtonType::West,
+    GamepadButtonType::C,
+    GamepadButtonType::Z,
+    GamepadButtonType::LeftTrigger,
+    GamepadButtonType::LeftTrigger2,
+    GamepadButtonType::RightTrigger,
+    GamepadButtonType::RightTrigger2,
+    GamepadButtonType::Select,
+    GamepadButtonType::Start,
+    GamepadButtonType::Mode,
+    GamepadButtonType::LeftThumb,
+    GamepadButtonType::RightThumb,
+    GamepadButtonType::DPadUp,
+    GamepadButtonType::DPadDown,
+    GamepadButtonType::DPadLeft,
+    GamepadButtonType::DPadRight,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Binding {
+    Key(KeyCode),
+    Button(GamepadButtonType),
+}
+
+fn parse_binding(name: &str) -> Option<Binding> {
+    if let Some(button) = name.strip_prefix("Pad") {
+        BUTTONS
+            .into_iter()
+            .find(|b| format!("{:?}", b) == button)
+            .map(Binding::Button)
+    } else {
+        KEYS.into_iter()
+            .find(|k| format!("{:?}", k) == name)
+            .map(Binding::Key)
+    }
+}
+
+#[derive(Resource)]
+pub struct Bindings {
+    actions: Vec<(Action, Vec<Binding>)>,
+    /// Stick deflection below this is ignored, the rest is scaled up to the full range.
+    pub deadzone: f32,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::*;
+        Self {
+            actions: vec![
+                (Action::Up, vec![Key(KeyCode::W)]),
+                (Action::Down, vec![Key(KeyCode::S)]),
+                (Action::Left, vec![Key(KeyCode::A)]),
+                (Action::Right, vec![Key(KeyCode::D)]),
+                (
+                    Action::StackUp,
+                    vec![Key(KeyCode::U), Button(GamepadButtonType::South)],
+                ),
+                (
+                    Action::StackDown,
+                    vec![Key(KeyCode::J), Button(GamepadButtonType::East)],
+                ),
+                (
+                    Action::Formation,
+                    vec![Key(KeyCode::F), Button(GamepadButtonType::North)],
+                ),
+                (Action::Save, vec![Key(KeyCode::Z)]),
+                (Action::Load, vec![Key(KeyCode::X)]),
+                (
+                    Action::Sprint,
+                    vec![
+                        Key(KeyCode::ShiftLeft),
+                        Button(GamepadButtonType::RightTrigger2),
+                    ],
+                ),
+                (
+                    Action::Slow,
+                    vec![
+                        Key(KeyCode::ControlLeft),
+                        Button(GamepadButtonType::LeftTrigger2),
+                    ],
+                ),
+            ],
+            deadzone: 0.2,
+        }
+    }
+}
+
+impl Bindings {
+    fn bindings(&self, action: Action) -> &[Binding] {
+        self.actions
+            .iter()
+            .find(|a| a.0 == action)
+            .map_or(&[], |a| &a.1)
+    }
+}
+
+/// Read a bindings file over the default bindings.
+pub fn parse_bindings(data: &str) -> Result<Bindings, String> {
+    let mut bindings = Bindings::default();
+    for (line_no, line) in data.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = || format!("line {}: can't parse `{}`", line_no + 1, line);
+        let t: Vec<_> = line.split_whitespace().collect();
+        if t[0] == "deadzone" {
+            bindings.deadzone = t
+                .get(1)
+                .and_then(|s| s.parse().ok())
+                .filter(|d| (0.0..1.0).contains(d))
+                .ok_or_else(error)?;
+            continue;
+        }
+        let action = ACTIONS.iter().find(|a| a.0 == t[0]).ok_or_else(error)?.1;
+        let keys = t[1..]
+            .iter()
+            .map(|name| parse_binding(name).ok_or_else(error))
+            .collect::<Result<_, _>>()?;
+        bindings.actions.retain(|a| a.0 != action);
+        bindings.actions.push((action, keys));
+    }
+    Ok(bindings)
+}
+
+/// Actions of the keyboard and every connected gamepad.
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    bindings: Res<'w, Bindings>,
+    keys: Res<'w, Input<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl ActionInput<'_> {
+    fn any(
+        &self,
+        action: Action,
+        key: impl Fn(KeyCode) -> bool,
+        button: impl Fn(GamepadButton) -> bool,
+    ) -> bool {
+        self.bindings.bindings(action).iter().any(|b| match *b {
+            Binding::Key(k) => key(k),
+            Binding::Button(t) => self
+                .gamepads
+                .iter()
+                .any(|gamepad| button(GamepadButton::new(gamepad, t))),
+        })
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.any(
+            action,
+            |k| self.keys.pressed(k),
+            |b| self.buttons.pressed(b),
+        )
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.any(
+            action,
+            |k| self.keys.just_pressed(k),
+            |b| self.buttons.just_pressed(b),
+        )
+    }
+
+    /// Left stick of the first gamepad out of its dead zone, x right and y up.
+    pub fn stick(&self) -> Vec2 {
+        let Some(gamepad) = self.gamepads.iter().next() else {
+            return Vec2::ZERO;
+        };
+        let axis = |t| self.axes.get(GamepadAxis::new(gamepad, t)).unwrap_or(0.0);
+        let v = Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        );
+        apply_deadzone(v, self.bindings.deadzone)
+    }
+}
+
+/// Zero inside the radial `deadzone`, outside it the length rescaled from `deadzone..1`
+/// to `0..1` keeping the direction.
+fn apply_deadzone(v: Vec2, deadzone: f32) -> Vec2 {
+    let len = v.length();
+    if len <= deadzone {
+        return Vec2::ZERO;
+    }
+    v * (((len - deadzone) / (1.0 - deadzone)).min(1.0) / len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_replace_the_defaults() {
+        let bindings =
+            parse_bindings("# comment\n\nup I PadDPadUp # arrows too\nstack_up\ndeadzone 0.3\n")
+                .unwrap();
+        assert_eq!(
+            bindings.bindings(Action::Up),
+            [
+                Binding::Key(KeyCode::I),
+                Binding::Button(GamepadButtonType::DPadUp)
+            ]
+        );
+        assert!(bindings.bindings(Action::StackUp).is_empty());
+        assert_eq!(bindings.bindings(Action::Down), [Binding::Key(KeyCode::S)]);
+        assert_eq!(bindings.deadzone, 0.3);
+    }
+
+    #[test]
+    fn bad_bindings_are_rejected() {
+        let error = |data| parse_bindings(data).err().unwrap();
+        assert_eq!(
+            error("up W\njump Space"),
+            "line 2: can't parse `jump Space`"
+        );
+        assert!(error("up Win").starts_with("line 1"));
+        assert!(error("up PadSouthEast").starts_with("line 1"));
+        assert!(error("up South").starts_with("line 1"));
+        assert!(parse_bindings("deadzone").is_err());
+        assert!(parse_bindings("deadzone x").is_err());
+        assert!(parse_bindings("deadzone -0.1").is_err());
+        assert!(parse_bindings("deadzone 1").is_err());
+        assert!(parse_bindings("deadzone 0").is_ok());
+    }
+
+    #[test]
+    fn deadzone_rescales_the_stick() {
+        assert_eq!(apply_deadzone(Vec2::new(0.1, -0.1), 0.2), Vec2::ZERO);
+        assert_eq!(apply_deadzone(Vec2::new(0.0, 0.2), 0.2), Vec2::ZERO);
+        let v = apply_deadzone(Vec2::new(0.6, 0.0), 0.2);
+        assert!((v - Vec2::new(0.5, 0.0)).length() < 1e-6, "{}", v);
+        // the direction is kept, full deflection stays full
+        let v = apply_deadzone(Vec2::new(-0.6, 0.8), 0.2);
+        assert!((v - Vec2::new(-0.6, 0.8)).length() < 1e-6, "{}", v);
+        let v = apply_deadzone(Vec2::new(0.9, 0.9), 0.2);
+        assert!((v.length() - 1.0).abs() < 1e-6 && v.x == v.y, "{}", v);
+        assert_eq!(
+            apply_deadzone(Vec2::new(0.3, 0.0), 0.0),
+            Vec2::new(0.3, 0.0)
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Camera controller, `C` switches between the modes. Right mouse drag rotates in orbit mode
+//! and pans in top-down mode, the wheel zooms and the arrow keys move the focus in both.
+
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    transform::TransformSystem,
+};
+use std::f32::consts::FRAC_PI_2;
+
+use snake_bevy::logic::*;
+
+const MIN_DISTANCE: f32 = 100.0;
+const MAX_DISTANCE: f32 = 5000.0;
+/// Radians per pixel of mouse motion.
+const ROTATE_SPEED: f32 = 0.005;
+/// Focus movement per second with the arrow keys, relative to the distance.
+const FLY_SPEED: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum CameraMode {
+    /// Keep the head of the player snake in view.
+    #[default]
+    Follow,
+    /// Keep every body of every snake in view.
+    Frame,
+    /// Free orbit around a focus that flies with the arrow keys, for debugging.
+    Orbit,
+    /// Looking straight down.
+    TopDown,
+}
+
+impl CameraMode {
+    fn next(self) -> Self {
+        match self {
+            CameraMode::Follow => CameraMode::Frame,
+            CameraMode::Frame => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::TopDown,
+            CameraMode::TopDown => CameraMode::Follow,
+        }
+    }
+}
+
+/// The camera looks at `focus` from `distance` away, turned by `yaw` around the up axis and
+/// `pitch` around the right axis.
+#[derive(Component)]
+pub struct CameraController {
+    pub mode: CameraMode,
+    pub focus: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    /// How fast follow and frame modes catch up, per second.
+    pub smoothing: f32,
+    /// Space around the snakes in frame mode, relative to their size.
+    pub margin: f32,
+    /// Pitch of the other modes, top-down mode looks straight down meanwhile.
+    saved_pitch: f32,
+}
+
+impl CameraController {
+    /// Looking at `focus` from `position`.
+    pub fn looking_at(position: Vec3, focus: Vec3) -> Self {
+        let v = focus - position;
+        Self {
+            mode: CameraMode::default(),
+            focus,
+            yaw: (-v.x).atan2(-v.z),
+            pitch: v.y.atan2(v.xz().length()),
+            distance: v.length(),
+            smoothing: 4.0,
+            margin: 1.2,
+            saved_pitch: 0.0,
+        }
+    }
+
+    /// Switch to the next mode, keeping the pitch of the other modes while in top-down mode.
+    pub fn next_mode(&mut self) {
+        let previous = self.mode;
+        self.mode = previous.next();
+        if self.mode == CameraMode::TopDown {
+            self.saved_pitch = self.pitch;
+        } else if previous == CameraMode::TopDown {
+            self.pitch = self.saved_pitch;
+        }
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+
+    fn transform(&self) -> Transform {
+        let rotation = self.rotation();
+        Transform {
+            translation: self.focus + rotation * Vec3::Z * self.distance,
+            rotation,
+            ..default()
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn camera_control(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mousebutton_input: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    query_player: Query<Entity, With<Player>>,
+    query_leader: Query<(Entity, &Leader)>,
+    query_body: Query<&Transform, Without<CameraController>>,
+    mut query_camera: Query<(&mut CameraController, &mut Transform, &Projection)>,
+) {
+    let delta_time = time.delta_seconds();
+    let motion: Vec2 = if mousebutton_input.pressed(MouseButton::Right) {
+        mouse_motion.read().map(|e| e.delta).sum()
+    } else {
+        mouse_motion.clear();
+        Vec2::ZERO
+    };
+    let zoom: f32 = mouse_wheel
+        .read()
+        .map(|e| match e.unit {
+            MouseScrollUnit::Line => e.y,
+            MouseScrollUnit::Pixel => e.y / 16.0,
+        })
+        .sum();
+    let arrows = Vec2::new(
+        keyboard_input.pressed(KeyCode::Right) as i32 as f32
+            - keyboard_input.pressed(KeyCode::Left) as i32 as f32,
+        keyboard_input.pressed(KeyCode::Up) as i32 as f32
+            - keyboard_input.pressed(KeyCode::Down) as i32 as f32,
+    );
+    // smoothing of follow and frame modes, independent of the frame rate
+    let catch_up = |smoothing: f32| 1.0 - (-smoothing * delta_time).exp();
+
+    for (mut controller, mut tm, projection) in query_camera.iter_mut() {
+        if keyboard_input.just_pressed(KeyCode::C) {
+            controller.next_mode();
+        }
+        let k = catch_up(controller.smoothing);
+        match controller.mode {
+            CameraMode::Follow => {
+                if let Ok(player) = query_player.get_single() {
+                    if let Ok(head) = query_body.get(player) {
+                        controller.focus = controller.focus.lerp(head.translation, k);
+                    }
+                }
+            }
+            CameraMode::Frame => {
+                let entities = query_leader.iter().flat_map(|(entity, leader)| {
+                    std::iter::once(entity).chain(leader.followers.iter().copied())
+                });
+                let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+                for body in query_body.iter_many(entities) {
+                    min = min.min(body.translation - RADIUS);
+                    max = max.max(body.translation + RADIUS);
+                }
+                if min.x <= max.x {
+                    let fov = match projection {
+                        Projection::Perspective(p) => p.fov,
+                        Projection::Orthographic(_) => FRAC_PI_2,
+                    };
+                    let radius = (max - min).length() * 0.5 * controller.margin;
+                    let distance = (radius / (fov * 0.5).tan()).max(MIN_DISTANCE);
+                    controller.focus = controller.focus.lerp((min + max) * 0.5, k);
+                    controller.distance += (distance - controller.distance) * k;
+                }
+            }
+            CameraMode::Orbit => {
+                controller.yaw -= motion.x * ROTATE_SPEED;
+                controller.pitch = (controller.pitch - motion.y * ROTATE_SPEED)
+                    .clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+                let fly = controller.rotation() * Vec3::new(arrows.x, 0.0, -arrows.y);
+                let distance = controller.distance;
+                controller.focus += fly * distance * FLY_SPEED * delta_time;
+            }
+            CameraMode::TopDown => {
+                controller.pitch = -FRAC_PI_2;
+                // pan so the ground under the cursor moves with it, roughly
+                let scale = controller.distance * 0.002;
+                let yaw = Quat::from_rotation_y(controller.yaw);
+                let pan = Vec3::new(-motion.x, 0.0, -motion.y) * scale
+                    + Vec3::new(arrows.x, 0.0, -arrows.y)
+                        * controller.distance
+                        * FLY_SPEED
+                        * delta_time;
+                controller.focus += yaw * pan;
+            }
+        }
+        if controller.mode != CameraMode::Frame {
+            controller.distance =
+                (controller.distance * 0.9f32.powf(zoom)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+        }
+        *tm = controller.transform();
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            camera_control.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_down_keeps_the_pitch_of_the_other_modes() {
+        let mut controller = CameraController::looking_at(Vec3::new(0.0, 750.0, 200.0), Vec3::ZERO);
+        let mut pitch = controller.pitch;
+        let mut modes = Vec::new();
+        for i in 0..8 {
+            controller.next_mode();
+            modes.push(controller.mode);
+            match controller.mode {
+                // what camera_control does every frame in top-down mode
+                CameraMode::TopDown => controller.pitch = -FRAC_PI_2,
+                mode => {
+                    assert_eq!(controller.pitch, pitch, "{:?}", mode);
+                    if mode == CameraMode::Orbit {
+                        // dragged in orbit mode
+                        pitch = -0.2 * i as f32;
+                        controller.pitch = pitch;
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            modes[..4],
+            [
+                CameraMode::Frame,
+                CameraMode::Orbit,
+                CameraMode::TopDown,
+                CameraMode::Follow
+            ]
+        );
+        assert_eq!(modes[..4], modes[4..]);
+    }
+}
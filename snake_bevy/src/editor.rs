@@ -0,0 +1,362 @@
+//! Level editor, toggled by `F2`. While it is on the mouse edits instead of moving the snake:
+//!
+//! - left click on the ground places with the current tool, on a portal, portal exit,
+//!   pickup or the spawn point it drags them
+//! - `Delete` deletes the portal or pickup under the cursor, the right mouse button is left
+//!   to the camera
+//! - `Tab` switches the tool between portal, pickup and spawn point
+//! - `F5` saves the edited level to `LEVEL_PATH`, read at the next start, collected pickups
+//!   included
+
+use bevy::{asset::io::file::FileAssetReader, pbr::NotShadowCaster, prelude::*};
+use std::fs;
+
+use super::{
+    movement_input, portal_color, render_portal, PickupRender, PortalExit, PortalMesh, LEVEL_PATH,
+};
+use snake_bevy::ground_mesh::GroundParam;
+use snake_bevy::level::{write_level, Level};
+use snake_bevy::logic::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Tool {
+    #[default]
+    Portal,
+    Pickup,
+    Spawn,
+}
+
+impl Tool {
+    fn next(self) -> Self {
+        match self {
+            Tool::Portal => Tool::Pickup,
+            Tool::Pickup => Tool::Spawn,
+            Tool::Spawn => Tool::Portal,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Portal => "portal",
+            Tool::Pickup => "pickup",
+            Tool::Spawn => "spawn point",
+        }
+    }
+}
+
+/// Something in the level the cursor can drag or delete.
+#[derive(Clone, Copy)]
+enum Grab {
+    Portal(Entity),
+    /// Exit of the portal entity.
+    PortalExit(Entity),
+    Pickup(Entity),
+    Spawn,
+}
+
+#[derive(Resource, Default)]
+struct Editor {
+    enabled: bool,
+    tool: Tool,
+    /// What is dragged and its offset from the cursor.
+    drag: Option<(Grab, Vec3)>,
+    /// Result of the last save, shown in the status line.
+    message: String,
+}
+
+#[derive(Component)]
+struct SpawnMarker;
+
+#[derive(Component)]
+struct Status;
+
+/// Where the cursor points on the ground, lifted by `RADIUS` like everything standing on it.
+fn cursor_position(
+    window: &Window,
+    camera: (&Camera, &GlobalTransform),
+    ground: &GroundParam,
+) -> Option<Vec3> {
+    let pos = window.cursor_position()?;
+    let ray = camera.0.viewport_to_world(camera.1, pos)?;
+    let hit = ground
+        .get()
+        .and_then(|g| g.ray_cast(ray, 999999.0))
+        .unwrap_or_else(|| ray.origin - ray.direction * (ray.origin.y / ray.direction.y));
+    Some(hit + Vec3::Y * RADIUS)
+}
+
+fn editor_keys(keyboard_input: Res<Input<KeyCode>>, mut editor: ResMut<Editor>, level: Res<Level>) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        editor.enabled = !editor.enabled;
+        editor.drag = None;
+        editor.message.clear();
+    }
+    if !editor.enabled {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        editor.tool = editor.tool.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        let path = FileAssetReader::get_base_path().join(LEVEL_PATH);
+        editor.message = match fs::write(&path, write_level(&level)) {
+            Ok(()) => format!("saved {}", path.display()),
+            Err(e) => format!("can't save {}: {}", path.display(), e),
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn edit_level(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut level: ResMut<Level>,
+    mut movement_input: ResMut<MovementInput>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mousebutton_input: Res<Input<MouseButton>>,
+    window: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    ground: GroundParam,
+    mut query_portal: Query<(Entity, &mut Portal, &mut Transform, &mut LevelIndex)>,
+    mut query_pickup: Query<
+        (Entity, &mut Transform, &mut LevelIndex),
+        (With<Pickup>, Without<Portal>),
+    >,
+    query_exit: Query<(Entity, &PortalExit)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    portal_mesh: Option<Res<PortalMesh>>,
+    pickup_render: Option<Res<PickupRender>>,
+) {
+    if !editor.enabled {
+        return;
+    }
+    movement_input.ray = None;
+    let Some(cursor) = cursor_position(window.single(), camera.single(), &ground) else {
+        return;
+    };
+    if !mousebutton_input.pressed(MouseButton::Left) {
+        editor.drag = None;
+    }
+    if let Some((grab, offset)) = editor.drag {
+        let p = cursor + offset;
+        match grab {
+            Grab::Portal(e) => {
+                if let Ok((_, _, mut tm, index)) = query_portal.get_mut(e) {
+                    tm.translation = p;
+                    level.portals[index.0].0 = p;
+                }
+            }
+            Grab::PortalExit(e) => {
+                if let Ok((_, mut portal, _, index)) = query_portal.get_mut(e) {
+                    portal.0 = p;
+                    level.portals[index.0].1 = p;
+                }
+            }
+            Grab::Pickup(e) => {
+                if let Ok((_, mut tm, index)) = query_pickup.get_mut(e) {
+                    tm.translation = p;
+                    level.pickups[index.0] = p;
+                }
+            }
+            Grab::Spawn => level.spawn = p,
+        }
+        return;
+    }
+
+    // nearest handle within RADIUS of the cursor on the ground plane
+    let handles = query_portal
+        .iter()
+        .flat_map(|(e, portal, tm, _)| {
+            [
+                (Grab::Portal(e), tm.translation),
+                (Grab::PortalExit(e), portal.0),
+            ]
+        })
+        .chain(
+            query_pickup
+                .iter()
+                .map(|(e, tm, _)| (Grab::Pickup(e), tm.translation)),
+        )
+        .chain(std::iter::once((Grab::Spawn, level.spawn)));
+    let mut select = None;
+    let mut select_d2 = RADIUS * RADIUS;
+    for (grab, p) in handles {
+        let d2 = (p - cursor).xz().length_squared();
+        if d2 < select_d2 {
+            select = Some((grab, p - cursor));
+            select_d2 = d2;
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        match select {
+            Some((Grab::Portal(e) | Grab::PortalExit(e), _)) => {
+                commands.entity(e).despawn();
+                for (exit, _) in query_exit.iter().filter(|(_, exit)| exit.0 == e) {
+                    commands.entity(exit).despawn();
+                }
+                // the last portal of the level takes the place of the deleted one
+                let (_, _, _, &LevelIndex(i)) = query_portal.get(e).unwrap();
+                level.portals.swap_remove(i);
+                let last = level.portals.len();
+                for (_, _, _, mut index) in query_portal.iter_mut() {
+                    if index.0 == last {
+                        index.0 = i;
+                    }
+                }
+            }
+            Some((Grab::Pickup(e), _)) => {
+                commands.entity(e).despawn();
+                let (_, _, &LevelIndex(i)) = query_pickup.get(e).unwrap();
+                level.pickups.swap_remove(i);
+                let last = level.pickups.len();
+                for (_, _, mut index) in query_pickup.iter_mut() {
+                    if index.0 == last {
+                        index.0 = i;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !mousebutton_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if select.is_some() {
+        editor.drag = select;
+        return;
+    }
+    match editor.tool {
+        Tool::Portal => {
+            // the exit follows the cursor until the button is released
+            let Some(mesh) = portal_mesh else {
+                return;
+            };
+            let portal_entity = commands
+                .spawn((Portal(cursor), LevelIndex(level.portals.len())))
+                .id();
+            level.portals.push((cursor, cursor));
+            let material = materials.add(StandardMaterial::from(portal_color(
+                query_portal.iter().len(),
+            )));
+            render_portal(
+                &mut commands,
+                portal_entity,
+                Transform::from_translation(cursor),
+                cursor,
+                &mesh.0,
+                material,
+            );
+            editor.drag = Some((Grab::PortalExit(portal_entity), Vec3::ZERO));
+        }
+        Tool::Pickup => {
+            let Some(render) = pickup_render else {
+                return;
+            };
+            commands.spawn((
+                PbrBundle {
+                    mesh: render.mesh.clone(),
+                    material: render.material.clone(),
+                    transform: Transform::from_translation(cursor),
+                    ..default()
+                },
+                Pickup,
+                LevelIndex(level.pickups.len()),
+            ));
+            level.pickups.push(cursor);
+        }
+        Tool::Spawn => {
+            level.spawn = cursor;
+            editor.drag = Some((Grab::Spawn, Vec3::ZERO));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_markers(
+    editor: Res<Editor>,
+    level: Res<Level>,
+    query_portal: Query<&Portal>,
+    mut query_exit: Query<(&PortalExit, &mut Transform), Without<SpawnMarker>>,
+    mut query_spawn: Query<(&mut Transform, &mut Visibility), With<SpawnMarker>>,
+    mut query_status: Query<(&mut Text, &mut Visibility), (With<Status>, Without<SpawnMarker>)>,
+) {
+    for (exit, mut tm) in query_exit.iter_mut() {
+        if let Ok(portal) = query_portal.get(exit.0) {
+            tm.translation = portal.0;
+        }
+    }
+    let visibility = if editor.enabled {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for (mut tm, mut v) in query_spawn.iter_mut() {
+        tm.translation = level.spawn;
+        *v = visibility;
+    }
+    for (mut text, mut v) in query_status.iter_mut() {
+        *v = visibility;
+        text.sections[0].value = format!(
+            "editor: {} (Tab tool, Delete remove, F5 save, F2 close) {}",
+            editor.tool.name(),
+            editor.message
+        );
+    }
+}
+
+fn setup_editor(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(
+                shape::Cylinder {
+                    radius: RADIUS,
+                    height: 2.0,
+                    resolution: 16,
+                    segments: 1,
+                }
+                .into(),
+            ),
+            material: materials.add(StandardMaterial::from(Color::rgba(1.0, 1.0, 1.0, 0.5))),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        NotShadowCaster,
+        SpawnMarker,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        }),
+        Status,
+    ));
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_systems(PostStartup, setup_editor)
+            .add_systems(
+                PreUpdate,
+                (editor_keys, edit_level).chain().after(movement_input),
+            )
+            .add_systems(PostUpdate, update_markers);
+    }
+}
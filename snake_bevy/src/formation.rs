@@ -0,0 +1,512 @@
+use bevy::prelude::*;
+use snake_move::*;
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use super::logic::{get_delay, get_distance, DISTANCE, RADIUS};
+
+/// How the bodies that left the walking chain are arranged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FormationShape {
+    /// Stacked on top of the body they climbed onto.
+    #[default]
+    Stack,
+    /// In a ring around the head.
+    Circle,
+    /// Side by side with the head.
+    LineAbreast,
+}
+
+impl FormationShape {
+    pub fn next(self) -> Self {
+        match self {
+            FormationShape::Stack => FormationShape::Circle,
+            FormationShape::Circle => FormationShape::LineAbreast,
+            FormationShape::LineAbreast => FormationShape::Stack,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FormationCommand {
+    /// Body `k` leaves the walking chain and climbs onto the body behind it,
+    /// which moves up to its place.
+    StackUp(usize),
+    /// The lowest member gathered on body `k` steps down and walks in its place,
+    /// in front of it.
+    StackDown(usize),
+    /// Rearrange the gathered members.
+    Shape(FormationShape),
+}
+
+struct Gathered {
+    entity: usize,
+    /// Entity this member was stacked onto.
+    anchor: usize,
+}
+
+/// Which entity of a snake walks as which `SnakeBody` and where the others are gathered.
+/// Entity 0 is the leader entity, entity `i` is `followers[i - 1]`.
+pub struct Formation {
+    pub shape: FormationShape,
+    /// Seconds a transition takes.
+    pub duration: f64,
+    walkers: Vec<usize>,
+    /// Bottom to top for every anchor.
+    gathered: Vec<Gathered>,
+    queue: VecDeque<FormationCommand>,
+    current: Option<FormationCommand>,
+    time: f64,
+    from: Vec<Vec3>,
+    head_target: Vec3,
+    /// Finished commands, true for the rejected ones.
+    done: Vec<(FormationCommand, bool)>,
+}
+
+impl Formation {
+    pub fn new(entity_count: usize) -> Self {
+        Self {
+            shape: FormationShape::Stack,
+            duration: 0.1,
+            walkers: (0..entity_count).collect(),
+            gathered: Vec::new(),
+            queue: VecDeque::new(),
+            current: None,
+            time: 0.0,
+            from: Vec::new(),
+            head_target: Vec3::ZERO,
+            done: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, command: FormationCommand) {
+        self.queue.push_back(command);
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty()
+    }
+
+    /// Whether the current transition moves the head, the snake can't be steered meanwhile.
+    pub fn moves_head(&self) -> bool {
+        matches!(
+            self.current,
+            Some(FormationCommand::StackUp(0)) | Some(FormationCommand::StackDown(0))
+        )
+    }
+
+    /// Finished commands since the last call, with whether they were rejected.
+    pub fn take_done(&mut self) -> std::vec::Drain<'_, (FormationCommand, bool)> {
+        self.done.drain(..)
+    }
+
+    /// A new entity walking at the tail.
+    pub fn push_walker(&mut self) {
+        self.walkers.push(self.walkers.len() + self.gathered.len());
+    }
+
+    pub fn transform_layer<F: Fn(Vec3) -> Vec3>(&mut self, layer: u32, head_layer: u32, f: F) {
+        if self.moves_head() && head_layer == layer {
+            self.head_target = f(self.head_target);
+        }
+    }
+
+    fn level(&self, index: usize) -> usize {
+        let anchor = self.gathered[index].anchor;
+        self.gathered[..index]
+            .iter()
+            .filter(|g| g.anchor == anchor)
+            .count()
+            + 1
+    }
+
+    fn slot(&self, index: usize, snake_head: &SnakeHead<YUp>, head_dir: Vec3) -> Vec3 {
+        let head_pos = snake_head.head_position();
+        let n = self.gathered.len();
+        match self.shape {
+            FormationShape::Stack => {
+                let anchor = self.gathered[index].anchor;
+                let mut pos = self.base_position(anchor, snake_head, head_dir);
+                pos.y += self.level(index) as f32 * RADIUS * 2.0;
+                pos
+            }
+            FormationShape::Circle => {
+                let r = (n as f32 * RADIUS * 1.1 / PI).max(RADIUS * 2.5);
+                let a = head_dir.z.atan2(head_dir.x) + index as f32 / n as f32 * PI * 2.0;
+                head_pos + Vec3::new(a.cos(), 0.0, a.sin()) * r
+            }
+            FormationShape::LineAbreast => {
+                let side = head_dir.cross(Vec3::Y).normalize_or_zero();
+                let k = (index / 2 + 1) as f32 * RADIUS * 2.2;
+                head_pos + side * [k, -k][index % 2]
+            }
+        }
+    }
+
+    /// Index of the body `entity` walks as, none while it is gathered in the formation.
+    pub fn walker_body(&self, entity: usize) -> Option<usize> {
+        self.walkers.iter().position(|&e| e == entity)
+    }
+
+    fn base_position(&self, entity: usize, snake_head: &SnakeHead<YUp>, head_dir: Vec3) -> Vec3 {
+        if let Some(i) = self.walker_body(entity) {
+            snake_head.bodies[i].position
+        } else {
+            let i = self
+                .gathered
+                .iter()
+                .position(|g| g.entity == entity)
+                .unwrap();
+            self.slot(i, snake_head, head_dir)
+        }
+    }
+
+    pub fn entity_position(
+        &self,
+        entity: usize,
+        snake_head: &SnakeHead<YUp>,
+        head_dir: Vec3,
+    ) -> Vec3 {
+        let pos = self.base_position(entity, snake_head, head_dir);
+        match self.from.get(entity) {
+            Some(from) if self.current.is_some() => {
+                let k = 1.0 - (self.time / self.duration).clamp(0.0, 1.0) as f32;
+                from.lerp(pos, k * k * (3.0 - 2.0 * k))
+            }
+            _ => pos,
+        }
+    }
+
+    fn reset_spacing(snake_head: &mut SnakeHead<YUp>) {
+        for (i, body) in snake_head.bodies.iter_mut().enumerate() {
+            body.delay = get_delay(i);
+            body.distance = get_distance(i);
+        }
+    }
+
+    /// Apply `command` to the chain, returns false if it can't be done: stacking up the tail,
+    /// stacking down where nothing is gathered, a body index past the tail or the shape
+    /// that is already set.
+    fn begin(
+        &mut self,
+        command: FormationCommand,
+        snake_head: &mut SnakeHead<YUp>,
+        head_dir: Vec3,
+    ) -> bool {
+        match command {
+            FormationCommand::StackUp(k) => {
+                if k + 1 >= snake_head.bodies.len() {
+                    return false;
+                }
+                let entity = self.walkers.remove(k);
+                let anchor = self.walkers[k];
+                let mut moved: Vec<_> = self
+                    .gathered
+                    .iter()
+                    .filter(|g| g.anchor == entity)
+                    .map(|g| g.entity)
+                    .collect();
+                self.gathered.retain(|g| g.anchor != entity);
+                moved.insert(0, entity);
+                self.gathered
+                    .extend(moved.into_iter().map(|entity| Gathered { entity, anchor }));
+                if k == 0 {
+                    snake_head.trim_head(1);
+                    self.head_target = snake_head.bodies.remove(0).position;
+                } else {
+                    snake_head.bodies.remove(k);
+                }
+            }
+            FormationCommand::StackDown(k) => {
+                if k >= snake_head.bodies.len() {
+                    return false;
+                }
+                let anchor = self.walkers[k];
+                let Some(index) = self.gathered.iter().position(|g| g.anchor == anchor) else {
+                    return false;
+                };
+                let entity = self.gathered.remove(index).entity;
+                for g in self.gathered.iter_mut().filter(|g| g.anchor == anchor) {
+                    g.anchor = entity;
+                }
+                self.walkers.insert(k, entity);
+                let body = snake_head.bodies[k].clone();
+                snake_head.bodies.insert(k, body);
+                snake_head.bodies[k].collision = false;
+                if k == 0 {
+                    self.head_target = snake_head.bodies[0].position + head_dir * DISTANCE;
+                }
+            }
+            FormationCommand::Shape(shape) => {
+                if shape == self.shape {
+                    return false;
+                }
+                self.shape = shape;
+            }
+        }
+        Self::reset_spacing(snake_head);
+        true
+    }
+
+    /// Start queued commands and advance the current transition. Returns the head position
+    /// while the transition moves the head.
+    pub fn update(
+        &mut self,
+        snake_head: &mut SnakeHead<YUp>,
+        head_dir: Vec3,
+        delta_time: f64,
+    ) -> Option<Vec3> {
+        while self.current.is_none() {
+            let Some(command) = self.queue.pop_front() else {
+                break;
+            };
+            let count = self.walkers.len() + self.gathered.len();
+            let from: Vec<_> = (0..count)
+                .map(|e| self.entity_position(e, snake_head, head_dir))
+                .collect();
+            if self.begin(command, snake_head, head_dir) {
+                self.current = Some(command);
+                self.time = self.duration;
+                self.from = from;
+            } else {
+                self.done.push((command, true));
+            }
+        }
+        let command = self.current?;
+        let mut head_pos = None;
+        if self.moves_head() {
+            let pos = snake_head.head_position();
+            head_pos = Some(if self.time > delta_time {
+                pos.lerp(self.head_target, (delta_time / self.time) as f32)
+            } else {
+                self.head_target
+            });
+        }
+        if self.time > delta_time {
+            self.time -= delta_time;
+        } else {
+            self.time = 0.0;
+            self.current = None;
+            self.from.clear();
+            if let FormationCommand::StackDown(k) = command {
+                snake_head.bodies[k].collision = true;
+            }
+            self.done.push((command, false));
+        }
+        head_pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::SPEED;
+
+    const DT: f64 = 1.0 / 60.0;
+
+    type FixPosition = fn(&SnakeBody<YUp>, Vec3, Vec3) -> (Vec3, u32);
+
+    fn step(snake_head: &mut SnakeHead<YUp>, head_pos: Vec3) {
+        snake_head.move_head(DT, head_pos, 0, MoveMode::Normal);
+        snake_head.update_body(RADIUS);
+        let max_move = DT as f32 * SPEED;
+        snake_head.solve_body(max_move, max_move * 0.1, RADIUS, None::<FixPosition>);
+    }
+
+    /// A snake of `count` bodies that walked along x for two seconds.
+    fn walked_snake(count: usize) -> (Formation, SnakeHead<YUp>) {
+        let bodies = (0..count)
+            .map(|i| {
+                let position = Vec3::new(-get_distance(i), RADIUS, 0.0);
+                SnakeBody::new(get_delay(i), get_distance(i), position)
+            })
+            .collect();
+        let mut snake_head = SnakeHead::new(bodies);
+        for _ in 0..120 {
+            let head_pos = snake_head.head_position() + Vec3::X * SPEED * DT as f32;
+            step(&mut snake_head, head_pos);
+        }
+        (Formation::new(count), snake_head)
+    }
+
+    /// Run `command` to its end, moving the head like the leader does but leaving the other
+    /// bodies where the transition put them. Returns whether it was rejected.
+    fn run(
+        formation: &mut Formation,
+        snake_head: &mut SnakeHead<YUp>,
+        command: FormationCommand,
+    ) -> bool {
+        formation.push(command);
+        while !formation.is_idle() {
+            if let Some(head_pos) = formation.update(snake_head, Vec3::X, DT) {
+                snake_head.move_head(DT, head_pos, 0, MoveMode::Normal);
+            }
+        }
+        let done: Vec<_> = formation.take_done().collect();
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].0, command);
+        done[0].1
+    }
+
+    /// Every entity walks or is gathered exactly once, the bodies line up with the walkers
+    /// and follow at the spacing of their index on the recorded path.
+    fn check_chain(formation: &Formation, snake_head: &SnakeHead<YUp>, entity_count: usize) {
+        assert_eq!(formation.walkers.len(), snake_head.bodies.len());
+        let mut entities: Vec<_> = formation
+            .walkers
+            .iter()
+            .copied()
+            .chain(formation.gathered.iter().map(|g| g.entity))
+            .collect();
+        entities.sort();
+        assert_eq!(entities, (0..entity_count).collect::<Vec<_>>());
+        for g in formation.gathered.iter() {
+            assert_ne!(g.entity, g.anchor);
+            assert!(g.anchor < entity_count);
+        }
+        assert!(snake_head.record_count() > 0);
+        for (i, body) in snake_head.bodies.iter().enumerate() {
+            assert_eq!(body.delay, get_delay(i));
+            assert_eq!(body.distance, get_distance(i));
+            assert!(body.segment() < snake_head.mode_count());
+            assert!(body.collision);
+        }
+    }
+
+    #[test]
+    fn stack_up_and_down_at_the_head() {
+        let (mut formation, mut snake_head) = walked_snake(6);
+        let head = snake_head.head_position();
+        assert!(!run(
+            &mut formation,
+            &mut snake_head,
+            FormationCommand::StackUp(0)
+        ));
+        check_chain(&formation, &snake_head, 6);
+        assert_eq!(snake_head.bodies.len(), 5);
+        assert_eq!(formation.walker_body(0), None);
+        assert_eq!(formation.walker_body(1), Some(0));
+        // the neck walked up to the place of the head, which sits on it now
+        assert!(snake_head.head_position().distance(head) < 1e-3);
+        assert!(snake_head.get_path().last().unwrap().distance(head) < 1e-3);
+        let stacked = formation.entity_position(0, &snake_head, Vec3::X);
+        assert!((stacked - head - Vec3::Y * RADIUS * 2.0).length() < 1e-3);
+
+        assert!(!run(
+            &mut formation,
+            &mut snake_head,
+            FormationCommand::StackDown(0)
+        ));
+        check_chain(&formation, &snake_head, 6);
+        assert_eq!(snake_head.bodies.len(), 6);
+        assert_eq!(formation.walkers, (0..6).collect::<Vec<_>>());
+        assert!(formation.gathered.is_empty());
+        let gap = snake_head.bodies[0].position - snake_head.bodies[1].position;
+        assert!((gap.length() - DISTANCE).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stack_up_in_the_middle() {
+        let (mut formation, mut snake_head) = walked_snake(6);
+        let records = snake_head.record_count();
+        let head = snake_head.head_position();
+        let positions: Vec<_> = snake_head.bodies.iter().map(|b| b.position).collect();
+        assert!(!run(
+            &mut formation,
+            &mut snake_head,
+            FormationCommand::StackUp(2)
+        ));
+        check_chain(&formation, &snake_head, 6);
+        // body 2 climbed onto body 3, which walks in its place now
+        assert_eq!(formation.walkers, [0, 1, 3, 4, 5]);
+        assert_eq!(formation.gathered.len(), 1);
+        assert_eq!(
+            (formation.gathered[0].entity, formation.gathered[0].anchor),
+            (2, 3)
+        );
+        // the head and the path didn't move, the bodies ahead of it neither
+        assert_eq!(snake_head.head_position(), head);
+        assert_eq!(snake_head.record_count(), records);
+        assert_eq!(snake_head.bodies[1].position, positions[1]);
+        let stacked = formation.entity_position(2, &snake_head, Vec3::X);
+        let base = snake_head.bodies[2].position;
+        assert!((stacked - base - Vec3::Y * RADIUS * 2.0).length() < 1e-3);
+
+        // body 1 takes the stack on it along onto the next walker
+        assert!(!run(
+            &mut formation,
+            &mut snake_head,
+            FormationCommand::StackUp(1)
+        ));
+        check_chain(&formation, &snake_head, 6);
+        assert_eq!(formation.walkers, [0, 3, 4, 5]);
+        assert!(formation.gathered.iter().all(|g| g.anchor == 3));
+        assert_eq!(formation.level(formation.gathered.len() - 1), 2);
+    }
+
+    #[test]
+    fn shape_enter_and_leave() {
+        let (mut formation, mut snake_head) = walked_snake(6);
+        run(
+            &mut formation,
+            &mut snake_head,
+            FormationCommand::StackUp(0),
+        );
+        run(
+            &mut formation,
+            &mut snake_head,
+            FormationCommand::StackUp(0),
+        );
+        let walkers = formation.walkers.clone();
+
+        let circle = FormationCommand::Shape(FormationShape::Circle);
+        assert!(!run(&mut formation, &mut snake_head, circle));
+        check_chain(&formation, &snake_head, 6);
+        assert_eq!(formation.walkers, walkers);
+        let head = snake_head.head_position();
+        let radius: Vec<_> = formation
+            .gathered
+            .iter()
+            .map(|g| {
+                formation
+                    .entity_position(g.entity, &snake_head, Vec3::X)
+                    .distance(head)
+            })
+            .collect();
+        assert!(radius
+            .iter()
+            .all(|r| (r - radius[0]).abs() < 1e-3 && *r >= RADIUS * 2.5));
+
+        let stack = FormationCommand::Shape(FormationShape::Stack);
+        assert!(!run(&mut formation, &mut snake_head, stack));
+        check_chain(&formation, &snake_head, 6);
+        for (i, g) in formation.gathered.iter().enumerate() {
+            let p = formation.entity_position(g.entity, &snake_head, Vec3::X);
+            assert!((p.xz() - head.xz()).length() < 1e-3);
+            assert!((p.y - head.y - formation.level(i) as f32 * RADIUS * 2.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn rejected_commands_leave_the_chain() {
+        let (mut formation, mut snake_head) = walked_snake(3);
+        let positions: Vec<_> = snake_head.bodies.iter().map(|b| b.position).collect();
+        for command in [
+            FormationCommand::StackUp(2),
+            FormationCommand::StackUp(7),
+            FormationCommand::StackDown(0),
+            FormationCommand::Shape(FormationShape::Stack),
+        ] {
+            assert!(
+                run(&mut formation, &mut snake_head, command),
+                "{:?}",
+                command
+            );
+            check_chain(&formation, &snake_head, 3);
+            assert_eq!(formation.walkers, [0, 1, 2]);
+        }
+        let after: Vec<_> = snake_head.bodies.iter().map(|b| b.position).collect();
+        assert_eq!(after, positions);
+    }
+}
@@ -1,3 +1,5 @@
+use bevy::ecs::system::SystemParam;
+use bevy::math::Affine3A;
 use bevy::prelude::*;
 use parry3d::math::{Isometry, Point, Vector};
 use parry3d::query::closest_points::{
@@ -5,21 +7,28 @@ use parry3d::query::closest_points::{
 };
 use parry3d::query::DefaultQueryDispatcher;
 use parry3d::query::{Ray, RayCast};
+use parry3d::shape::FeatureId;
 use parry3d::shape::{Ball, TriMesh, TypedSimdCompositeShape};
 
 #[derive(Resource)]
 pub struct GroundMesh {
     pub mesh: TriMesh,
+    /// Layer (floor) id of each triangle.
+    pub layers: Vec<u32>,
 }
 
 impl GroundMesh {
     pub fn new(mesh: TriMesh) -> Self {
-        Self { mesh }
+        let layers = vec![0; mesh.indices().len()];
+        Self { mesh, layers }
     }
 
+    /// Every object or group in the obj data becomes a layer, numbered in file order.
     pub fn from_obj(data: &str) -> Option<GroundMesh> {
         let mut v: Vec<Point<f32>> = Vec::new();
         let mut ind = Vec::new();
+        let mut layers = Vec::new();
+        let mut layer = 0;
         for line in data.lines() {
             let mut t = line.split(' ');
             match t.next() {
@@ -35,21 +44,43 @@ impl GroundMesh {
                     for i in fv.iter_mut() {
                         *i = t.next()?.parse::<u32>().ok()? - 1;
                     }
-                    ind.push(fv)
+                    ind.push(fv);
+                    layers.push(layer);
+                }
+                Some("o") | Some("g") if layers.last() == Some(&layer) => {
+                    layer += 1;
                 }
                 _ => {}
             }
         }
-        let ground_mesh = GroundMesh::new(TriMesh::new(v, ind));
+        let mut ground_mesh = GroundMesh::new(TriMesh::new(v, ind));
+        ground_mesh.layers = layers;
         Some(ground_mesh)
     }
 
-    pub fn fix_position(&self, mut p: Vec3, d: f32, h: f32) -> Vec3 {
+    fn face_layer(&self, feature: FeatureId) -> u32 {
+        match feature {
+            FeatureId::Face(i) => self.layers[i as usize % self.layers.len()],
+            _ => 0,
+        }
+    }
+
+    /// Snap `p` onto the ground, returns the fixed position and the layer it stands on.
+    /// `layer` is kept when nothing is found within `d`.
+    pub fn fix_position(&self, p: Vec3, d: f32, h: f32, layer: u32) -> (Vec3, u32) {
+        self.snap(p, d, h).unwrap_or((p, layer))
+    }
+
+    fn snap(&self, mut p: Vec3, d: f32, h: f32) -> Option<(Vec3, u32)> {
         p.y -= h;
         let ray = Ray::new(Point::new(p.x, p.y + d, p.z), Vector::new(0.0, -1.0, 0.0));
-        let mut p1 = p;
-        if let Some(t) = self.mesh.cast_local_ray(&ray, d * 2.0, false) {
-            p1 = Vec3::new(ray.origin.x, ray.origin.y - t, ray.origin.z);
+        let mut found = None;
+        if let Some(hit) = self
+            .mesh
+            .cast_local_ray_and_get_normal(&ray, d * 2.0, false)
+        {
+            let p1 = Vec3::new(ray.origin.x, ray.origin.y - hit.toi, ray.origin.z);
+            found = Some((p1, self.face_layer(hit.feature)));
         } else {
             let ball = Ball::new(0.001);
             let pos12 = Isometry::translation(p.x, p.y, p.z);
@@ -61,14 +92,28 @@ impl GroundMesh {
                 &ball,
                 d,
             );
-            if let Some((_, (_, ClosestPoints::WithinMargin(p2, _)))) =
+            if let Some((_, (i, ClosestPoints::WithinMargin(p2, _)))) =
                 self.mesh.typed_qbvh().traverse_best_first(&mut visitor)
             {
-                p1 = Vec3::new(p2.x, p2.y, p2.z);
+                found = Some((Vec3::new(p2.x, p2.y, p2.z), self.layers[i as usize]));
             };
         }
-        p1.y += h;
-        p1
+        found.map(|(p1, layer1)| (p1 + Vec3::Y * h, layer1))
+    }
+
+    /// Point and upward normal of the ground under `p`, looking `d` around `h` below it.
+    fn ground_normal(&self, p: Vec3, d: f32, h: f32) -> Option<(Vec3, Vec3)> {
+        let ray = Ray::new(
+            Point::new(p.x, p.y - h + d, p.z),
+            Vector::new(0.0, -1.0, 0.0),
+        );
+        self.mesh
+            .cast_local_ray_and_get_normal(&ray, d * 2.0, false)
+            .map(|hit| {
+                let p1 = ray.point_at(hit.toi);
+                let n = Vec3::new(hit.normal.x, hit.normal.y, hit.normal.z);
+                (Vec3::new(p1.x, p1.y, p1.z), if n.y < 0.0 { -n } else { n })
+            })
     }
 
     pub fn ray_cast(&self, ray: bevy::prelude::Ray, d: f32) -> Option<Vec3> {
@@ -81,4 +126,187 @@ impl GroundMesh {
             Vec3::new(p.x, p.y, p.z)
         })
     }
+
+    /// Like `ray_cast`, but passes through every surface not on `layer`.
+    pub fn ray_cast_layer(&self, ray: bevy::prelude::Ray, d: f32, layer: u32) -> Option<Vec3> {
+        let mut ray = Ray::new(
+            Point::new(ray.origin.x, ray.origin.y, ray.origin.z),
+            Vector::new(ray.direction.x, ray.direction.y, ray.direction.z),
+        );
+        let mut remain = d;
+        while let Some(hit) = self.mesh.cast_local_ray_and_get_normal(&ray, remain, false) {
+            let p = ray.point_at(hit.toi);
+            if self.face_layer(hit.feature) == layer {
+                return Some(Vec3::new(p.x, p.y, p.z));
+            }
+            let skip = hit.toi + 0.01;
+            remain -= skip;
+            if remain <= 0.0 {
+                break;
+            }
+            ray.origin = ray.point_at(skip);
+        }
+        None
+    }
+}
+
+/// A ground mesh placed by its entity's `Transform`, which may change every frame
+/// (moving platforms, elevators, rotating bridges). All its triangles are on `layer`.
+/// The transform is expected to be rigid, scale is not supported.
+#[derive(Component)]
+pub struct GroundCollider {
+    pub mesh: GroundMesh,
+    pub layer: u32,
+    prev: Option<Affine3A>,
+}
+
+impl GroundCollider {
+    pub fn new(mut mesh: GroundMesh, layer: u32) -> Self {
+        mesh.layers.fill(layer);
+        Self {
+            mesh,
+            layer,
+            prev: None,
+        }
+    }
+
+    /// Returns how the collider moved since the last call.
+    pub fn take_delta(&mut self, tm: &Transform) -> Option<Affine3A> {
+        let cur = tm.compute_affine();
+        let delta = self
+            .prev
+            .filter(|prev| *prev != cur)
+            .map(|prev| cur * prev.inverse());
+        self.prev = Some(cur);
+        delta
+    }
+}
+
+struct GroundPart<'a> {
+    mesh: &'a GroundMesh,
+    transform: Option<(Affine3A, Affine3A)>,
+}
+
+impl GroundPart<'_> {
+    fn to_local(&self, p: Vec3) -> Vec3 {
+        self.transform.map_or(p, |(_, inv)| inv.transform_point3(p))
+    }
+
+    fn to_world(&self, p: Vec3) -> Vec3 {
+        self.transform.map_or(p, |(tm, _)| tm.transform_point3(p))
+    }
+
+    fn vector_to_world(&self, v: Vec3) -> Vec3 {
+        self.transform.map_or(v, |(tm, _)| tm.transform_vector3(v))
+    }
+
+    fn local_ray(&self, ray: bevy::prelude::Ray) -> bevy::prelude::Ray {
+        match self.transform {
+            Some((_, inv)) => bevy::prelude::Ray {
+                origin: inv.transform_point3(ray.origin),
+                direction: inv.transform_vector3(ray.direction),
+            },
+            None => ray,
+        }
+    }
+}
+
+/// All ground of the world: the static `GroundMesh` and every `GroundCollider`, in world space.
+pub struct Ground<'a> {
+    parts: Vec<GroundPart<'a>>,
+}
+
+impl<'a> Ground<'a> {
+    pub fn new(
+        mesh: Option<&'a GroundMesh>,
+        colliders: impl Iterator<Item = (&'a GroundCollider, &'a Transform)>,
+    ) -> Option<Self> {
+        let parts: Vec<_> = mesh
+            .map(|mesh| GroundPart {
+                mesh,
+                transform: None,
+            })
+            .into_iter()
+            .chain(colliders.map(|(collider, tm)| {
+                let tm = tm.compute_affine();
+                GroundPart {
+                    mesh: &collider.mesh,
+                    transform: Some((tm, tm.inverse())),
+                }
+            }))
+            .collect();
+        (!parts.is_empty()).then_some(Self { parts })
+    }
+
+    /// Same as `GroundMesh::fix_position` over all parts. The nearest snap on `layer` wins,
+    /// so a body doesn't drop onto a part below it, unless another part is higher and the
+    /// body steps up onto it.
+    pub fn fix_position(&self, p: Vec3, d: f32, h: f32, layer: u32) -> (Vec3, u32) {
+        let nearer =
+            |a: &(Vec3, u32), b: &(Vec3, u32)| a.0.distance_squared(p) < b.0.distance_squared(p);
+        let mut own: Option<(Vec3, u32)> = None;
+        let mut any: Option<(Vec3, u32)> = None;
+        for part in self.parts.iter() {
+            let Some((p1, layer1)) = part.mesh.snap(part.to_local(p), d, h) else {
+                continue;
+            };
+            let snap = (part.to_world(p1), layer1);
+            if layer1 == layer && own.is_none_or(|own| nearer(&snap, &own)) {
+                own = Some(snap);
+            }
+            if any.is_none_or(|any| nearer(&snap, &any)) {
+                any = Some(snap);
+            }
+        }
+        match (own, any) {
+            (Some(own), Some(any)) if any.0.y <= own.0.y => own,
+            (_, any) => any.unwrap_or((p, layer)),
+        }
+    }
+
+    /// Upward normal of the nearest ground under `p`, like `fix_position` looks for it.
+    pub fn normal(&self, p: Vec3, d: f32, h: f32) -> Option<Vec3> {
+        self.parts
+            .iter()
+            .filter_map(|part| {
+                part.mesh
+                    .ground_normal(part.to_local(p), d, h)
+                    .map(|(p1, n)| (part.to_world(p1), part.vector_to_world(n)))
+            })
+            .min_by(|a, b| a.0.distance_squared(p).total_cmp(&b.0.distance_squared(p)))
+            .map(|(_, n)| n)
+    }
+
+    fn nearest_hit<F>(&self, ray: bevy::prelude::Ray, f: F) -> Option<Vec3>
+    where
+        F: Fn(&GroundMesh, bevy::prelude::Ray) -> Option<Vec3>,
+    {
+        self.parts
+            .iter()
+            .filter_map(|part| f(part.mesh, part.local_ray(ray)).map(|p| part.to_world(p)))
+            .min_by(|a, b| {
+                a.distance_squared(ray.origin)
+                    .total_cmp(&b.distance_squared(ray.origin))
+            })
+    }
+
+    pub fn ray_cast(&self, ray: bevy::prelude::Ray, d: f32) -> Option<Vec3> {
+        self.nearest_hit(ray, |mesh, ray| mesh.ray_cast(ray, d))
+    }
+
+    pub fn ray_cast_layer(&self, ray: bevy::prelude::Ray, d: f32, layer: u32) -> Option<Vec3> {
+        self.nearest_hit(ray, |mesh, ray| mesh.ray_cast_layer(ray, d, layer))
+    }
+}
+
+#[derive(SystemParam)]
+pub struct GroundParam<'w, 's> {
+    mesh: Option<Res<'w, GroundMesh>>,
+    colliders: Query<'w, 's, (&'static GroundCollider, &'static Transform)>,
+}
+
+impl GroundParam<'_, '_> {
+    pub fn get(&self) -> Option<Ground<'_>> {
+        Ground::new(self.mesh.as_deref(), self.colliders.iter())
+    }
 }
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use super::logic::RADIUS;
+
+/// Where the snake starts, the portals and pickups around it and the computer controlled
+/// snakes, read by `setup_logic`.
+#[derive(Resource, Clone)]
+pub struct Level {
+    /// Head position, the followers are lined up behind it along -x.
+    pub spawn: Vec3,
+    /// Entrance and exit of every portal.
+    pub portals: Vec<(Vec3, Vec3)>,
+    pub pickups: Vec<Vec3>,
+    /// Start and waypoints of every computer controlled snake.
+    pub snakes: Vec<(Vec3, Vec<Vec3>)>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        let portals = [
+            (0.0, 200.0, 0.0, -200.0),
+            (150.0, 180.0, -150.0, -180.0),
+            (0.0, -210.0, 200.0, 0.0),
+        ];
+        let pickups = [(-200.0, 100.0), (220.0, -120.0), (-120.0, -240.0)];
+        Self {
+            spawn: Vec3::new(0.0, RADIUS, 0.0),
+            portals: portals
+                .iter()
+                .map(|p| (Vec3::new(p.0, RADIUS, -p.1), Vec3::new(p.2, RADIUS, -p.3)))
+                .collect(),
+            pickups: pickups
+                .iter()
+                .map(|p| Vec3::new(p.0, RADIUS, -p.1))
+                .collect(),
+            snakes: Vec::new(),
+        }
+    }
+}
+
+/// Parse a level with one `spawn x y z`, `portal x y z x y z`, `pickup x y z` or
+/// `snake x y z [waypoint x y z]...` per line. The spawn point keeps its default when the
+/// level has none.
+pub fn parse_level(data: &str) -> Result<Level, String> {
+    let mut level = Level {
+        portals: Vec::new(),
+        pickups: Vec::new(),
+        ..default()
+    };
+    for (line_no, line) in data.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = || format!("line {}: can't parse `{}`", line_no + 1, line);
+        let t: Vec<_> = line.split_whitespace().collect();
+        let v: Vec<f32> = t[1..]
+            .iter()
+            .map(|s| s.parse().map_err(|_| error()))
+            .collect::<Result<_, _>>()?;
+        match (t[0], v.len()) {
+            ("spawn", 3) => level.spawn = Vec3::from_slice(&v),
+            ("portal", 6) => level
+                .portals
+                .push((Vec3::from_slice(&v), Vec3::from_slice(&v[3..]))),
+            ("pickup", 3) => level.pickups.push(Vec3::from_slice(&v)),
+            ("snake", n) if n >= 3 && n % 3 == 0 => level.snakes.push((
+                Vec3::from_slice(&v),
+                v[3..].chunks(3).map(Vec3::from_slice).collect(),
+            )),
+            _ => return Err(error()),
+        }
+    }
+    Ok(level)
+}
+
+/// The text `parse_level` reads back.
+pub fn write_level(level: &Level) -> String {
+    let p = |v: Vec3| format!("{} {} {}", v.x, v.y, v.z);
+    let mut s = format!("spawn {}\n", p(level.spawn));
+    for &(entrance, exit) in level.portals.iter() {
+        s += &format!("portal {} {}\n", p(entrance), p(exit));
+    }
+    for &pickup in level.pickups.iter() {
+        s += &format!("pickup {}\n", p(pickup));
+    }
+    for (start, waypoints) in level.snakes.iter() {
+        s += &format!("snake {}", p(*start));
+        for &w in waypoints.iter() {
+            s += &format!(" {}", p(w));
+        }
+        s += "\n";
+    }
+    s
+}
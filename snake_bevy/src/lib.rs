@@ -1,17 +1,58 @@
-#![allow(dead_code)]
+use bevy::ecs::system::SystemState;
 pub use bevy::prelude::App;
 use bevy::prelude::*;
 use bevy::utils::Duration;
 
+// Shared with the `snake_bevy` binary, not a stable api.
+#[doc(hidden)]
+pub mod ai;
 // mod character_move;
-mod ground_mesh;
-mod logic;
+#[doc(hidden)]
+pub mod formation;
+#[doc(hidden)]
+pub mod ground_mesh;
+#[doc(hidden)]
+pub mod level;
+#[doc(hidden)]
+pub mod logic;
+#[doc(hidden)]
+pub mod metrics;
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub mod save;
+mod script;
+#[doc(hidden)]
+pub mod skeleton;
 
-use ground_mesh::GroundMesh;
+use ai::SnakeAi;
+use formation::{FormationCommand, FormationShape};
+use ground_mesh::{GroundCollider, GroundMesh};
+use level::{parse_level, Level};
 use logic::*;
+use metrics::Metrics;
+use script::{parse_script, Command};
+use snake_move::{FollowMode, SpacingModel};
+
+/// Internals used by the benchmarks and tests, not a stable api.
+#[doc(hidden)]
+pub mod bench {
+    pub use super::ground_mesh::{Ground, GroundCollider, GroundMesh};
+    use bevy::prelude::Vec3;
+
+    /// `move_on_ground` of the movement systems on a single static mesh.
+    pub fn move_on_ground(mesh: &GroundMesh, from: Vec3, to: Vec3, layer: u32) -> (Vec3, u32) {
+        let ground = Ground::new(Some(mesh), std::iter::empty()).unwrap();
+        super::logic::move_on_ground(from, to, layer, &ground)
+    }
+}
 
 pub fn init(ground: Option<&str>) -> App {
     let mut app = App::new();
+    // the demo pickups of the default level would grow the snake in every scenario
+    app.insert_resource(Level {
+        pickups: Vec::new(),
+        ..default()
+    });
     app.init_resource::<Time>()
         .add_plugins((bevy::core::TaskPoolPlugin::default(), SnakeLogicPlugin));
     if let Some(ground) = ground.and_then(GroundMesh::from_obj) {
@@ -47,7 +88,7 @@ pub fn update(
     app.update();
     let (leader, leader_tm) = app
         .world
-        .query::<(&Leader, &Transform)>()
+        .query_filtered::<(&Leader, &Transform), With<Player>>()
         .single(&app.world);
     let followers = leader.followers.clone();
     let mut ipos = position.chunks_mut(3);
@@ -60,6 +101,328 @@ pub fn update(
     }
 }
 
+/// Rotation quaternion (4) of the head and every follower after the last `update`, facing
+/// along the path with up from the ground.
+pub fn get_rotations(app: &mut App, rotations: &mut [f32]) {
+    let (leader, leader_tm) = app
+        .world
+        .query_filtered::<(&Leader, &Transform), With<Player>>()
+        .single(&app.world);
+    let followers = leader.followers.clone();
+    let mut irot = rotations.chunks_mut(4);
+    if let Some(q) = irot.next() {
+        q.copy_from_slice(&leader_tm.rotation.to_array());
+    }
+    let mut tm = app.world.query::<&Transform>();
+    for (q, f) in irot.zip(tm.iter_many(&app.world, followers.iter())) {
+        q.copy_from_slice(&f.rotation.to_array());
+    }
+}
+
+/// Translation (3) and rotation quaternion (4) of `count` bones evenly spaced along a curve
+/// through the head and the walking followers, for driving a rigged model. The first bone
+/// is at the head, followers gathered in a formation are left out.
+pub fn get_bones(app: &mut App, count: u32, bones: &mut [f32]) {
+    let leader = app
+        .world
+        .query_filtered::<&Leader, With<Player>>()
+        .single(&app.world);
+    let points = skeleton::chain_points(leader);
+    let up = leader.snake_head.bodies[0].up;
+    for (b, bone) in bones
+        .chunks_mut(7)
+        .zip(skeleton::resample_bones(&points, count as usize, up))
+    {
+        b[..3].copy_from_slice(bone.translation.as_ref());
+        b[3..].copy_from_slice(&bone.rotation.to_array());
+    }
+}
+
+/// Accelerate the head by `acceleration` and brake by `deceleration` (units per second
+/// squared) up to `SPEED`, turning at most `turn_rate` radians per second. `sprint` and
+/// `slow` scale the top speed for `set_pace`. An `acceleration` of 0 turns it off, the head
+/// moving at `SPEED` and turning at once, otherwise all of them have to be greater than 0.
+pub fn set_locomotion(
+    app: &mut App,
+    acceleration: f32,
+    deceleration: f32,
+    turn_rate: f32,
+    sprint: f32,
+    slow: f32,
+) -> Result<(), String> {
+    if !(acceleration >= 0.0 && acceleration.is_finite()) {
+        return Err(format!(
+            "acceleration {} is negative or not finite",
+            acceleration
+        ));
+    }
+    if acceleration > 0.0 {
+        for (name, value) in [
+            ("deceleration", deceleration),
+            ("turn_rate", turn_rate),
+            ("sprint", sprint),
+            ("slow", slow),
+        ] {
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(format!("{} {} is not greater than 0", name, value));
+            }
+        }
+    }
+    let mut leader = app
+        .world
+        .query_filtered::<&mut Leader, With<Player>>()
+        .single_mut(&mut app.world);
+    leader.locomotion = (acceleration > 0.0).then_some(Locomotion {
+        speed: SPEED,
+        acceleration,
+        deceleration,
+        turn_rate,
+        sprint,
+        slow,
+    });
+    Ok(())
+}
+
+/// 0: normal, 1: sprint, 2: slow, for `set_locomotion`.
+pub fn set_pace(app: &mut App, pace: u32) {
+    if let Some(mut m) = app.world.get_resource_mut::<MovementInput>() {
+        m.pace = match pace {
+            1 => Pace::Sprint,
+            2 => Pace::Slow,
+            _ => Pace::Normal,
+        };
+    }
+}
+
+/// Make the axis input of `update` relative to a camera with rotation quaternion (4),
+/// x to its right and y where it looks on the ground. All zeros turn it off.
+pub fn set_camera_rotation(app: &mut App, rotation: &[f32]) {
+    let rotation = Quat::from_slice(&rotation[..4]);
+    if let Some(mut m) = app.world.get_resource_mut::<MovementInput>() {
+        m.camera_yaw = if rotation.length_squared() > 0.0 {
+            camera_yaw(rotation.normalize())
+        } else {
+            None
+        };
+    }
+}
+
+/// Add a computer controlled snake of `bodies` bodies with the head at `position` (3),
+/// following `waypoints` (3 each) in a loop or wandering without them. It seeks pickups
+/// and avoids the other snakes. Returns an id for `get_snake`.
+pub fn add_ai_snake(app: &mut App, position: &[f32], bodies: u32, waypoints: &[f32]) -> u64 {
+    let mut state = SystemState::<Commands>::new(&mut app.world);
+    let mut commands = state.get_mut(&mut app.world);
+    let snake = spawn_snake(
+        &mut commands,
+        Vec3::from_slice(&position[..3]),
+        bodies as usize,
+        Some(Locomotion::default()),
+    );
+    let waypoints = waypoints.chunks_exact(3).map(Vec3::from_slice).collect();
+    commands.entity(snake).insert((
+        SnakeInput::default(),
+        SnakeAi::new(waypoints, snake.index()),
+    ));
+    state.apply(&mut app.world);
+    snake.to_bits()
+}
+
+/// Positions (3) of the head and followers of a snake added by `add_ai_snake`, returns
+/// the number of bodies written.
+pub fn get_snake(app: &mut App, snake: u64, positions: &mut [f32]) -> u32 {
+    let Some(leader) = app.world.get::<Leader>(Entity::from_bits(snake)) else {
+        return 0;
+    };
+    let mut count = 0;
+    for (body, p) in leader.snake_head.bodies.iter().zip(positions.chunks_mut(3)) {
+        p.copy_from_slice(body.position.as_ref());
+        count += 1;
+    }
+    count
+}
+
+/// Add a movable ground from obj data, all its triangles are on `layer`. Snakes on `layer`
+/// are carried with it, so it must not be a layer of the static ground or another movable
+/// ground. Returns an id for `set_ground_transform`, none for bad data or a used layer.
+pub fn add_ground(app: &mut App, ground: &str, layer: u32) -> Option<u64> {
+    let used = app
+        .world
+        .get_resource::<GroundMesh>()
+        .is_some_and(|mesh| mesh.layers.contains(&layer))
+        || app
+            .world
+            .query::<&GroundCollider>()
+            .iter(&app.world)
+            .any(|collider| collider.layer == layer);
+    if used {
+        return None;
+    }
+    let mesh = GroundMesh::from_obj(ground)?;
+    let entity = app
+        .world
+        .spawn((Transform::IDENTITY, GroundCollider::new(mesh, layer)))
+        .id();
+    Some(entity.to_bits())
+}
+
+/// Set translation (3) and rotation quaternion (4) of a ground added by `add_ground`.
+pub fn set_ground_transform(app: &mut App, ground: u64, transform: &[f32]) {
+    if let Some(mut tm) = app.world.get_mut::<Transform>(Entity::from_bits(ground)) {
+        tm.translation = Vec3::from_slice(&transform[..3]);
+        tm.rotation = Quat::from_slice(&transform[3..7]);
+    }
+}
+
+fn push_formation(app: &mut App, command: FormationCommand) {
+    let mut leader = app
+        .world
+        .query_filtered::<&mut Leader, With<Player>>()
+        .single_mut(&mut app.world);
+    leader.formation.push(command);
+}
+
+/// Stack up (`up`) or down at the head, queued after running transitions.
+pub fn stack(app: &mut App, up: bool) {
+    stack_at(app, 0, up);
+}
+
+/// Stack up (`up`) or down at body `index`, queued after running transitions.
+pub fn stack_at(app: &mut App, index: u32, up: bool) {
+    let index = index as usize;
+    push_formation(
+        app,
+        if up {
+            FormationCommand::StackUp(index)
+        } else {
+            FormationCommand::StackDown(index)
+        },
+    );
+}
+
+/// 0: stack, 1: circle, 2: line abreast.
+pub fn set_formation(app: &mut App, shape: u32) {
+    let shape = match shape {
+        1 => FormationShape::Circle,
+        2 => FormationShape::LineAbreast,
+        _ => FormationShape::Stack,
+    };
+    push_formation(app, FormationCommand::Shape(shape));
+}
+
+/// Whether a formation transition is running or queued.
+pub fn is_stacking(app: &mut App) -> bool {
+    let leader = app
+        .world
+        .query_filtered::<&Leader, With<Player>>()
+        .single(&app.world);
+    !leader.formation.is_idle()
+}
+
+/// Whether a formation transition of a snake added by `add_ai_snake` is running or queued.
+pub fn is_snake_stacking(app: &mut App, snake: u64) -> bool {
+    app.world
+        .get::<Leader>(Entity::from_bits(snake))
+        .is_some_and(|leader| !leader.formation.is_idle())
+}
+
+/// Seconds a formation transition takes, greater than 0.
+pub fn set_stack_duration(app: &mut App, duration: f32) -> Result<(), String> {
+    if !(duration > 0.0 && duration.is_finite()) {
+        return Err(format!("stack duration {} is not greater than 0", duration));
+    }
+    let mut leader = app
+        .world
+        .query_filtered::<&mut Leader, With<Player>>()
+        .single_mut(&mut app.world);
+    leader.formation.duration = duration as f64;
+    Ok(())
+}
+
+/// Stop the head at its own bodies instead of pushing them.
+pub fn set_block_head(app: &mut App, block: bool) {
+    let mut leader = app
+        .world
+        .query_filtered::<&mut Leader, With<Player>>()
+        .single_mut(&mut app.world);
+    leader.block_head = block;
+}
+
+/// How followers keep behind the head: 0 where the head was a delay ago, 1 a fixed
+/// distance behind it, 2 both, with the delay and distance scaled by the weights.
+pub fn set_follow_mode(app: &mut App, mode: u32, time_weight: f32, distance_weight: f32) {
+    let follow = match mode {
+        0 => FollowMode::Time,
+        1 => FollowMode::Distance,
+        _ => FollowMode::Hybrid {
+            time: time_weight,
+            distance: distance_weight,
+        },
+    };
+    let mut leader = app
+        .world
+        .query_filtered::<&mut Leader, With<Player>>()
+        .single_mut(&mut app.world);
+    leader.set_follow_mode(follow);
+}
+
+/// Scale the body distances from `min` with the head standing still to `max` at full speed,
+/// following the head speed with a spring of `stiffness`. `min` and `max` of 1 turn it off.
+/// The scales and stiffness can't be negative.
+pub fn set_spacing(app: &mut App, min: f32, max: f32, stiffness: f32) -> Result<(), String> {
+    for (name, value) in [("min", min), ("max", max), ("stiffness", stiffness)] {
+        if !(value >= 0.0 && value.is_finite()) {
+            return Err(format!(
+                "spacing {} {} is negative or not finite",
+                name, value
+            ));
+        }
+    }
+    let mut leader = app
+        .world
+        .query_filtered::<&mut Leader, With<Player>>()
+        .single_mut(&mut app.world);
+    leader.snake_head.spacing = (min != 1.0 || max != 1.0).then_some(SpacingModel {
+        min,
+        max,
+        speed: SPEED,
+        stiffness,
+    });
+    Ok(())
+}
+
+/// Spawn point, portals and pickups from level data written by the editor of the
+/// `snake_bevy` binary. Only has an effect before the first `update`.
+pub fn load_level(app: &mut App, level: &str) -> Result<(), String> {
+    app.insert_resource(parse_level(level)?);
+    Ok(())
+}
+
+pub fn add_pickup(app: &mut App, position: &[f32]) {
+    app.world.spawn((
+        Transform::from_translation(Vec3::from_slice(&position[..3])),
+        Pickup,
+    ));
+}
+
+/// Number of bodies including the head, grows when pickups are collected.
+pub fn body_count(app: &mut App) -> u32 {
+    let leader = app
+        .world
+        .query_filtered::<&Leader, With<Player>>()
+        .single(&app.world);
+    leader.followers.len() as u32 + 1
+}
+
+pub fn get_pickups(app: &mut App) -> Box<[f32]> {
+    let mut query_pickup = app.world.query_filtered::<&Transform, With<Pickup>>();
+    let v: Vec<_> = query_pickup
+        .iter(&app.world)
+        .flat_map(|tm| tm.translation.to_array())
+        .collect();
+    v.into_boxed_slice()
+}
+
 pub fn get_portals(app: &mut App) -> Box<[f32]> {
     let mut query_portal = app.world.query::<(&Portal, &Transform)>();
     let v: Vec<_> = query_portal
@@ -79,17 +442,71 @@ pub fn get_portals(app: &mut App) -> Box<[f32]> {
 }
 
 pub fn get_path(app: &mut App, path: &mut [f32]) -> u32 {
-    let leader = app.world.query::<&Leader>().single(&app.world);
+    let leader = app
+        .world
+        .query_filtered::<&Leader, With<Player>>()
+        .single(&app.world);
     let mut count = 0;
     for (p0, p1) in leader.snake_head.get_path().zip(path.chunks_mut(3)) {
-        p1.copy_from_slice(from_snake(p0).as_ref());
+        p1.copy_from_slice(p0.as_ref());
         count += 1;
     }
     count
 }
 
+/// Run a scenario script (see the `snake_sim` binary) for `frames` frames,
+/// `on_frame` gets the body positions after every frame.
+pub fn run_script(
+    app: &mut App,
+    script: &str,
+    frames: u32,
+    delta_time: f32,
+    mut on_frame: impl FnMut(&[f32]),
+) -> Result<(), String> {
+    let script = parse_script(script)?;
+    // An empty first frame runs the startup systems that spawn the snake.
+    update(app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    let mut ray = [0.0; 6];
+    let mut axis = [0.0; 2];
+    let mut next = 0;
+    let mut positions = Vec::new();
+    for frame in 0..frames {
+        while next < script.len() && script[next].0 <= frame {
+            match script[next].1 {
+                Command::Axis(x, y) => axis = [x, y],
+                Command::Target(x, z) => ray = [x, 10000.0, z, 0.0, -1.0, 0.0],
+                Command::Release => ray = [0.0; 6],
+                Command::Stack(up) => stack(app, up),
+                Command::Formation(shape) => set_formation(app, shape),
+            }
+            next += 1;
+        }
+        positions.resize(body_count(app) as usize * 3, 0.0);
+        update(app, delta_time, &ray, &axis, &mut positions);
+        on_frame(&positions);
+    }
+    Ok(())
+}
+
+/// Start collecting movement metrics of the snake, see `metrics_report`.
+pub fn enable_metrics(app: &mut App) {
+    app.insert_resource(Metrics::default());
+}
+
+/// Path deviation, overlap, jitter, stuck frames and teleport delays of every body
+/// since `enable_metrics`, one line per body.
+pub fn metrics_report(app: &mut App) -> String {
+    app.world
+        .get_resource::<Metrics>()
+        .map(|m| m.report())
+        .unwrap_or_default()
+}
+
 pub fn get_targets(app: &mut App, targets: &mut [f32]) {
-    let leader = app.world.query::<&Leader>().single(&app.world);
+    let leader = app
+        .world
+        .query_filtered::<&Leader, With<Player>>()
+        .single(&app.world);
     for (body, p1) in leader
         .snake_head
         .bodies
@@ -97,6 +514,6 @@ pub fn get_targets(app: &mut App, targets: &mut [f32]) {
         .skip(1)
         .zip(targets.chunks_mut(3))
     {
-        p1.copy_from_slice(from_snake(body.target).as_ref());
+        p1.copy_from_slice(body.target.as_ref());
     }
 }
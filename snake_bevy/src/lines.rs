@@ -1,3 +1,14 @@
+//! Movement debugger overlay, each layer toggled by a key:
+//!
+//! - `P` recorded head path
+//! - `T` body to target
+//! - `1` path segment starts, green for walking and magenta for teleports
+//! - `2` portal links
+//! - `3` planned move of every body (yellow) and how far it could move (orange)
+//! - `4` collision circles, red while overlapping another body
+//! - `5` ground probes, from the body down to where `fix_position` puts it
+//! - `F1` text panel with record counts and system timings
+
 use bevy::{
     pbr::{MaterialPipeline, MaterialPipelineKey},
     prelude::*,
@@ -8,90 +19,277 @@ use bevy::{
             AsBindGroup, PolygonMode, RenderPipelineDescriptor, ShaderRef,
             SpecializedMeshPipelineError,
         },
+        view::NoFrustumCulling,
     },
 };
+use std::f32::consts::TAU;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use snake_bevy::ground_mesh::GroundParam;
+use snake_bevy::logic::*;
+use snake_move::MoveMode;
 
-use super::logic::*;
+/// The movement systems in the order they run, timed by `SystemTimings`.
+const TIMED_SYSTEMS: [(&str, SnakeSystem); 8] = [
+    ("ground_carry", SnakeSystem::GroundCarry),
+    ("ai_steer", SnakeSystem::AiSteer),
+    ("leader_move", SnakeSystem::LeaderMove),
+    ("body_move", SnakeSystem::BodyMove),
+    ("snake_hit", SnakeSystem::SnakeHit),
+    ("pickup_collect", SnakeSystem::PickupCollect),
+    ("collect_metrics", SnakeSystem::CollectMetrics),
+    ("update_skeletons", SnakeSystem::UpdateSkeletons),
+];
+
+/// Drawn moves are this much longer than the move of one frame.
+const MOVE_SCALE: f32 = 5.0;
 
 #[derive(Component, Default)]
 struct Lines();
 
+#[derive(Component)]
+struct Panel;
+
+const LAYER_KEYS: [KeyCode; 7] = [
+    KeyCode::P,
+    KeyCode::T,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+];
+
+/// Which layers of the overlay are shown, in the order of `LAYER_KEYS`.
+#[derive(Resource, Default)]
+struct Overlay {
+    layers: [bool; LAYER_KEYS.len()],
+    panel: bool,
+}
+
+impl Overlay {
+    fn path(&self) -> bool {
+        self.layers[0]
+    }
+    fn targets(&self) -> bool {
+        self.layers[1]
+    }
+    fn modes(&self) -> bool {
+        self.layers[2]
+    }
+    fn portals(&self) -> bool {
+        self.layers[3]
+    }
+    fn moves(&self) -> bool {
+        self.layers[4]
+    }
+    fn collision(&self) -> bool {
+        self.layers[5]
+    }
+    fn probes(&self) -> bool {
+        self.layers[6]
+    }
+}
+
+/// Line list vertices of the overlay mesh.
+struct LineBuffer<'a> {
+    positions: &'a mut Vec<[f32; 3]>,
+    colors: &'a mut Vec<[f32; 4]>,
+}
+
+impl LineBuffer<'_> {
+    fn line(&mut self, a: Vec3, b: Vec3, color: Color) {
+        self.positions.extend([a.to_array(), b.to_array()]);
+        let color = color.as_rgba_f32();
+        self.colors.extend([color, color]);
+    }
+
+    /// Horizontal circle.
+    fn circle(&mut self, center: Vec3, radius: f32, color: Color) {
+        const SEGMENTS: usize = 24;
+        let point = |i: usize| {
+            let a = i as f32 / SEGMENTS as f32 * TAU;
+            center + Vec3::new(a.cos(), 0.0, a.sin()) * radius
+        };
+        for i in 0..SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    fn cross(&mut self, p: Vec3, size: f32, color: Color) {
+        self.line(p - Vec3::X * size, p + Vec3::X * size, color);
+        self.line(p - Vec3::Z * size, p + Vec3::Z * size, color);
+    }
+}
+
+fn plane_to_world(v: Vec2) -> Vec3 {
+    Vec3::new(v.x, 0.0, -v.y)
+}
+
+fn toggle_overlay(keyboard_input: Res<Input<KeyCode>>, mut overlay: ResMut<Overlay>) {
+    for (key, show) in LAYER_KEYS.iter().zip(overlay.layers.iter_mut()) {
+        if keyboard_input.just_pressed(*key) {
+            *show = !*show;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        overlay.panel = !overlay.panel;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_lines(
+    overlay: Res<Overlay>,
     query_leader: Query<&Leader>,
     query_lines: Query<&Handle<Mesh>, With<Lines>>,
+    query_portal: Query<(&Portal, &Transform)>,
     mut meshes: ResMut<Assets<Mesh>>,
     query_tm: Query<&Transform>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut show: Local<(bool, bool)>,
+    ground: GroundParam,
 ) {
-    if keyboard_input.just_pressed(KeyCode::P) {
-        show.0 = !show.0;
-    }
-    if keyboard_input.just_pressed(KeyCode::T) {
-        show.1 = !show.1;
-    }
-    if show.0 || show.1 {
-        if let Some(mesh) = meshes.get_mut(query_lines.single()) {
-            if let Some(VertexAttributeValues::Float32x3(positions)) =
-                mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
-            {
-                positions.clear();
+    let Some(mesh) = meshes.get_mut(query_lines.single()) else {
+        return;
+    };
+    let Some(VertexAttributeValues::Float32x3(mut positions)) =
+        mesh.remove_attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return;
+    };
+    let Some(VertexAttributeValues::Float32x4(mut colors)) =
+        mesh.remove_attribute(Mesh::ATTRIBUTE_COLOR)
+    else {
+        return;
+    };
+    positions.clear();
+    colors.clear();
+    let mut lines = LineBuffer {
+        positions: &mut positions,
+        colors: &mut colors,
+    };
+
+    if overlay.portals() {
+        for (portal, tm) in query_portal.iter() {
+            let color = Color::CYAN;
+            lines.line(tm.translation, portal.0, color);
+            lines.circle(tm.translation, RADIUS, color);
+            lines.circle(portal.0, RADIUS * 0.5, color);
+        }
+    }
+    let ground = ground.get();
+    for leader in query_leader.iter() {
+        let snake_head = &leader.snake_head;
+        let bodies = &snake_head.bodies;
+        if overlay.path() {
+            let path: Vec<_> = snake_head.get_path().collect();
+            for w in path.windows(2) {
+                lines.line(w[0], w[1], Color::BLACK);
             }
-            if let Some(VertexAttributeValues::Float32x4(colors)) =
-                mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
-            {
-                colors.clear();
+        }
+        if overlay.targets() {
+            let iter_tm = query_tm.iter_many(&leader.followers);
+            for (i, (body, tm)) in bodies.iter().skip(1).zip(iter_tm).enumerate() {
+                let color = super::color(i + 1) * 0.9;
+                lines.line(tm.translation, body.target, color);
             }
-            for leader in query_leader.iter() {
-                if show.0 {
-                    let mut num = 0;
-                    if let Some(VertexAttributeValues::Float32x3(positions)) =
-                        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
-                    {
-                        for p in leader.snake_head.get_path() {
-                            if positions.len() > 1 {
-                                positions.push(*positions.last().unwrap());
-                            }
-                            positions.push(from_snake(p).to_array());
-                        }
-                        num = positions.len();
-                    }
-                    if let Some(VertexAttributeValues::Float32x4(colors)) =
-                        mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
-                    {
-                        for _ in 0..num {
-                            colors.push(Color::BLACK.as_rgba_f32());
-                        }
-                    }
-                }
-                if show.1 {
-                    let iter_tm = query_tm.iter_many(&leader.followers);
-                    for (i, (body, tm)) in leader
-                        .snake_head
-                        .bodies
-                        .iter()
-                        .skip(1)
-                        .zip(iter_tm)
-                        .enumerate()
-                    {
-                        if let Some(VertexAttributeValues::Float32x3(positions)) =
-                            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
-                        {
-                            positions.push(tm.translation.to_array());
-                            positions.push(from_snake(body.target).to_array());
-                            if let Some(VertexAttributeValues::Float32x4(colors)) =
-                                mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
-                            {
-                                let color = (super::color(i + 1) * 0.9).as_rgba_f32();
-                                colors.push(color);
-                                colors.push(color);
-                            }
-                        }
-                    }
+        }
+        if overlay.modes() {
+            for (_, mode, position) in snake_head.get_modes() {
+                let color = match mode {
+                    MoveMode::Normal => Color::GREEN,
+                    MoveMode::Teleport => Color::FUCHSIA,
+                };
+                lines.line(position, position + Vec3::Y * RADIUS * 2.0, color);
+                lines.cross(position, RADIUS * 0.5, color);
+            }
+        }
+        for (i, body) in bodies.iter().enumerate() {
+            let pos = body.position;
+            if overlay.moves() {
+                let delta = plane_to_world(body.delta()) * MOVE_SCALE;
+                lines.line(pos, pos + delta, Color::YELLOW);
+                lines.circle(pos, body.max_move() * MOVE_SCALE, Color::ORANGE);
+            }
+            if overlay.collision() {
+                let overlapping = bodies.iter().enumerate().any(|(j, other)| {
+                    i != j
+                        && body.layer == other.layer
+                        && pos.distance(other.position) < RADIUS * 2.0
+                });
+                let color = if overlapping {
+                    Color::RED
+                } else {
+                    Color::WHITE
+                };
+                lines.circle(pos, RADIUS, color);
+            }
+            if overlay.probes() {
+                if let Some(g) = ground.as_ref() {
+                    let (p, _) = g.fix_position(pos, 3.0, RADIUS, body.layer);
+                    let foot = p - Vec3::Y * RADIUS;
+                    lines.line(pos, foot, Color::BLUE);
+                    lines.cross(foot, RADIUS * 0.3, Color::BLUE);
                 }
             }
         }
     }
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+}
+
+/// Run time of each of `TIMED_SYSTEMS`, smoothed over frames.
+#[derive(Resource, Default)]
+struct SystemTimings {
+    times: [Duration; TIMED_SYSTEMS.len()],
+    last: Option<Instant>,
+}
+
+/// Runs before system `i` of `TIMED_SYSTEMS` and after the one before it.
+fn mark_time(i: usize, timings: &mut SystemTimings) {
+    let now = Instant::now();
+    if let Some(last) = timings.last.filter(|_| i > 0) {
+        let t = &mut timings.times[i - 1];
+        *t = (*t * 7 + (now - last)) / 8;
+    }
+    timings.last = Some(now);
+}
+
+fn update_panel(
+    overlay: Res<Overlay>,
+    timings: Res<SystemTimings>,
+    query_leader: Query<&Leader>,
+    mut query_panel: Query<(&mut Text, &mut Visibility), With<Panel>>,
+) {
+    let Ok((mut text, mut visibility)) = query_panel.get_single_mut() else {
+        return;
+    };
+    *visibility = if overlay.panel {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if !overlay.panel {
+        return;
+    }
+    let mut s = String::new();
+    for (i, leader) in query_leader.iter().enumerate() {
+        let snake_head = &leader.snake_head;
+        writeln!(
+            s,
+            "snake {}: {} bodies, {} records, {} segments, spacing {:.2}",
+            i,
+            snake_head.bodies.len(),
+            snake_head.record_count(),
+            snake_head.mode_count(),
+            snake_head.spacing_scale(),
+        )
+        .unwrap();
+    }
+    for ((name, _), time) in TIMED_SYSTEMS.iter().zip(timings.times) {
+        writeln!(s, "{:<16} {:>7.1} us", name, time.as_secs_f64() * 1e6).unwrap();
+    }
+    text.sections[0].value = s;
 }
 
 fn setup(
@@ -114,8 +312,26 @@ fn setup(
             material: materials.add(LineMaterial {}),
             ..default()
         },
+        NoFrustumCulling,
         Lines(),
     ));
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        }),
+        Panel,
+    ));
 }
 
 #[derive(Asset, TypePath, Default, AsBindGroup, Debug, Clone)]
@@ -143,7 +359,21 @@ pub struct LinesPlugin;
 impl Plugin for LinesPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(MaterialPlugin::<LineMaterial>::default())
+            .init_resource::<Overlay>()
+            .init_resource::<SystemTimings>()
             .add_systems(PostStartup, setup)
-            .add_systems(PostUpdate, update_lines);
+            .add_systems(PreUpdate, toggle_overlay)
+            .add_systems(PostUpdate, (update_lines, update_panel));
+        for i in 0..=TIMED_SYSTEMS.len() {
+            let mut marker = (move |mut timings: ResMut<SystemTimings>| mark_time(i, &mut timings))
+                .into_configs();
+            if i > 0 {
+                marker = marker.after(TIMED_SYSTEMS[i - 1].1);
+            }
+            if i < TIMED_SYSTEMS.len() {
+                marker = marker.before(TIMED_SYSTEMS[i].1);
+            }
+            app.add_systems(Update, marker);
+        }
     }
 }
@@ -1,140 +1,275 @@
 use bevy::prelude::*;
 
-use super::ground_mesh::GroundMesh;
+use super::ai::{ai_steer, SnakeAi};
+use super::formation::{Formation, FormationCommand};
+use super::ground_mesh::{Ground, GroundCollider, GroundParam};
+use super::level::Level;
+use super::metrics::{collect_metrics, Metrics};
+use super::skeleton::update_skeletons;
+use bevy::math::Affine3A;
 // use super::character_move::character_move;
 use snake_move::*;
 
 use std::iter;
 
-pub fn to_snake(v: Vec3) -> Vec3 {
-    Vec3::new(v.x, -v.z, v.y)
+pub const RADIUS: f32 = 30.0;
+pub const DISTANCE: f32 = 80.0;
+pub const SPEED: f32 = 300.0;
+/// How the followers of a new snake keep behind the head.
+pub const FOLLOW_MODE: FollowMode = FollowMode::Hybrid {
+    time: 1.0,
+    distance: 1.0,
+};
+
+/// Speed modifier of the head input, see `Locomotion`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Pace {
+    #[default]
+    Normal,
+    Sprint,
+    Slow,
 }
 
-pub fn from_snake(v: Vec3) -> Vec3 {
-    Vec3::new(v.x, v.z, -v.y)
+/// How the head of a snake speeds up, slows down and turns, instead of moving at `SPEED`
+/// and turning at once.
+#[derive(Clone, Copy, Debug)]
+pub struct Locomotion {
+    /// Top speed at `Pace::Normal`.
+    pub speed: f32,
+    /// Units per second squared.
+    pub acceleration: f32,
+    pub deceleration: f32,
+    /// Radians per second, the head arcs into a new direction.
+    pub turn_rate: f32,
+    /// Speed scale of `Pace::Sprint` and `Pace::Slow`.
+    pub sprint: f32,
+    pub slow: f32,
 }
 
-pub const RADIUS: f32 = 30.0;
-pub const DISTANCE: f32 = 80.0;
-pub const SPEED: f32 = 300.0;
+impl Default for Locomotion {
+    fn default() -> Self {
+        Self {
+            speed: SPEED,
+            acceleration: SPEED * 3.0,
+            deceleration: SPEED * 4.0,
+            turn_rate: std::f32::consts::TAU,
+            sprint: 1.6,
+            slow: 0.5,
+        }
+    }
+}
 
-fn get_delay(i: usize) -> f32 {
+impl Locomotion {
+    fn max_speed(&self, pace: Pace) -> f32 {
+        self.speed
+            * match pace {
+                Pace::Normal => 1.0,
+                Pace::Sprint => self.sprint,
+                Pace::Slow => self.slow,
+            }
+    }
+}
+
+pub fn get_delay(i: usize) -> f32 {
     i as f32 * 0.1
 }
 
-fn get_distance(i: usize) -> f32 {
+pub fn get_distance(i: usize) -> f32 {
     i as f32 * DISTANCE
 }
 
 #[derive(Component)]
 pub struct Portal(pub Vec3);
 
+/// The snake driven by `MovementInput` and the flat api.
+#[derive(Component)]
+pub struct Player;
+
+/// Input of one snake, used instead of `MovementInput` when the leader entity has it.
+#[derive(Component, Default)]
+pub struct SnakeInput {
+    /// Click to move target.
+    pub target: Option<Vec3>,
+    /// Direction on the ground, at most one long for full speed.
+    pub direction: Vec3,
+    pub pace: Pace,
+}
+
+/// An item that grows the snake by one body when the head touches it.
+#[derive(Component)]
+pub struct Pickup;
+
+/// Index of a portal or pickup in `Level::portals` or `Level::pickups`, for the editor.
+#[derive(Component, Clone, Copy)]
+pub struct LevelIndex(pub usize);
+
+#[derive(Event)]
+pub struct PickupEvent {
+    pub leader: Entity,
+    pub pickup: Entity,
+}
+
+/// The head of `leader` ran into body `body` of `other`, `other` is `leader` itself for
+/// self collision. `relative_speed` is the closing speed per second.
+#[derive(Event)]
+pub struct SnakeHitEvent {
+    pub leader: Entity,
+    pub other: Entity,
+    pub body: usize,
+    pub point: Vec3,
+    pub relative_speed: f32,
+}
+
+/// Sent when a formation transition of `leader` is finished, or right away when `command`
+/// is `rejected` because it can't be done, leaving the formation as it was.
+#[derive(Event)]
+pub struct FormationFinished {
+    pub leader: Entity,
+    pub command: FormationCommand,
+    pub rejected: bool,
+}
+
+/// Sent after a follower entity is added at the tail of a snake.
+#[derive(Event)]
+pub struct GrowEvent {
+    pub leader: Entity,
+    pub follower: Entity,
+}
+
 #[derive(Component)]
 pub struct Leader {
-    pub snake_head: SnakeHead,
+    pub snake_head: SnakeHead<YUp>,
     pub followers: Vec<Entity>,
-    stack_state: i32,
-    stack_time: f64,
-    stack_pos: Vec3,
+    /// Stop the head at its own bodies instead of pushing them away.
+    pub block_head: bool,
+    contacts: Vec<SnakeContact<YUp>>,
+    /// Bodies put on their target by the "fix stuck" branch of the last `body_move`.
+    stuck: Vec<usize>,
+    pub formation: Formation,
     head_dir: Vec3,
+    /// Moves the head when set, otherwise it goes at `SPEED` and turns at once.
+    pub locomotion: Option<Locomotion>,
+    /// Current speed of the head under `locomotion`.
+    speed: f32,
 }
 
 impl Leader {
-    fn new(snake_bodies: Vec<SnakeBody>, followers: Vec<Entity>) -> Self {
+    fn new(snake_bodies: Vec<SnakeBody<YUp>>, followers: Vec<Entity>) -> Self {
+        let formation = Formation::new(followers.len() + 1);
         Self {
             snake_head: SnakeHead::new(snake_bodies),
             followers,
-            stack_state: 0,
-            stack_time: 0.0,
-            stack_pos: Vec3::ZERO,
+            block_head: false,
+            contacts: Vec::new(),
+            stuck: Vec::new(),
+            formation,
             head_dir: Vec3::X,
+            locomotion: None,
+            speed: 0.0,
         }
     }
 
-    fn can_move(&self) -> bool {
-        self.stack_state == 0
+    /// Fastest the head can go, the bodies may move as fast to keep up.
+    fn speed_limit(&self) -> f32 {
+        self.locomotion
+            .map_or(SPEED, |l| l.max_speed(Pace::Sprint).max(l.speed))
     }
 
-    fn stack_up(&mut self) {
-        if self.stack_state == 0 && self.snake_head.bodies.len() > 1 {
-            self.stack_state = 1;
-            self.stack_time = 0.1;
-            self.snake_head.trim_head(1);
-            self.stack_pos = self.snake_head.bodies.remove(0).position;
-            for (i, body) in self.snake_head.bodies.iter_mut().enumerate() {
-                body.delay = get_delay(i);
-                body.distance = get_distance(i);
+    /// Move of the head under `locomotion` toward `target` or along `axis`, which is at
+    /// most one long for full speed.
+    fn locomote(
+        &mut self,
+        locomotion: Locomotion,
+        pace: Pace,
+        target: Option<Vec3>,
+        axis: Vec3,
+        delta_time: f32,
+    ) -> Vec3 {
+        let max_speed = locomotion.max_speed(pace);
+        let (wish, mut wish_speed, stop) = match target {
+            Some(p) => {
+                let mut v = p - self.snake_head.head_position();
+                v.y = 0.0;
+                let len = v.length();
+                // brake in time to stop at the target
+                let arrive = (2.0 * locomotion.deceleration * len).sqrt();
+                (v, max_speed.min(arrive), len)
             }
+            None => (axis, max_speed * axis.length().min(1.0), f32::MAX),
+        };
+        if let Some(wish) = wish.try_normalize() {
+            let max_turn = locomotion.turn_rate * delta_time;
+            self.head_dir = if self.head_dir.angle_between(wish) <= max_turn {
+                wish
+            } else {
+                let sign = self.head_dir.cross(wish).y.signum();
+                Quat::from_rotation_y(sign * max_turn) * self.head_dir
+            };
+        } else {
+            wish_speed = 0.0;
         }
+        let rate = if wish_speed > self.speed {
+            locomotion.acceleration
+        } else {
+            locomotion.deceleration
+        };
+        let change = (wish_speed - self.speed).clamp(-rate * delta_time, rate * delta_time);
+        self.speed += change;
+        self.head_dir * (self.speed * delta_time).min(stop)
     }
 
-    fn stack_down(&mut self) {
-        if self.stack_state == 0 && self.snake_head.bodies.len() <= self.followers.len() {
-            self.stack_state = -1;
-            self.stack_time = 0.1;
-            self.stack_pos =
-                self.snake_head.bodies[0].position + to_snake(self.head_dir * DISTANCE);
+    /// Run the formation, returns false if the head was moved by it.
+    fn update_formation(&mut self, delta_time: f64) -> bool {
+        let head_pos = self
+            .formation
+            .update(&mut self.snake_head, self.head_dir, delta_time);
+        if let Some(head_pos) = head_pos {
+            let layer = self.snake_head.bodies[0].layer;
             self.snake_head
-                .bodies
-                .insert(0, self.snake_head.bodies[0].clone());
-            self.snake_head.bodies[0].collision = false;
-            for (i, body) in self.snake_head.bodies.iter_mut().enumerate() {
-                body.delay = get_delay(i);
-                body.distance = get_distance(i);
-            }
+                .move_head(delta_time, head_pos, layer, MoveMode::Normal);
         }
+        head_pos.is_none()
     }
 
-    fn update_stack(&mut self, delta_time: f64) {
-        let mut head_pos = self.snake_head.head_position();
-        match self.stack_state {
-            1 => {
-                if self.stack_time > delta_time {
-                    head_pos = head_pos.lerp(self.stack_pos, (delta_time / self.stack_time) as f32);
-                    self.stack_time -= delta_time;
-                } else {
-                    head_pos = self.stack_pos;
-                    self.stack_time = 0.0;
-                    self.stack_state = 0;
-                }
-            }
-            -1 => {
-                if self.stack_time > delta_time {
-                    head_pos = head_pos.lerp(self.stack_pos, (delta_time / self.stack_time) as f32);
-                    self.stack_time -= delta_time;
-                } else {
-                    head_pos = self.stack_pos;
-                    self.stack_time = 0.0;
-                    self.stack_state = 0;
-                    self.snake_head.bodies[0].collision = true;
-                }
-            }
-            _ => {}
+    fn grow(&mut self, follower: Entity) {
+        let i = self.snake_head.bodies.len();
+        self.snake_head.push_body(get_delay(i), get_distance(i));
+        self.followers.push(follower);
+        self.formation.push_walker();
+    }
+
+    /// Follow mode of every body, the head ignores it and grown bodies copy the tail.
+    pub fn set_follow_mode(&mut self, follow: FollowMode) {
+        for body in self.snake_head.bodies.iter_mut() {
+            body.follow = follow;
         }
-        self.snake_head
-            .move_head(delta_time, head_pos, MoveMode::Normal);
+    }
+
+    fn transform_layer(&mut self, layer: u32, delta: Affine3A) {
+        let f = |p| delta.transform_point3(p);
+        let head_layer = self.snake_head.bodies[0].layer;
+        self.formation.transform_layer(layer, head_layer, f);
+        self.snake_head.transform_layer(layer, f);
+    }
+
+    pub fn stuck_bodies(&self) -> &[usize] {
+        &self.stuck
     }
 
     fn entity_position(&self, i: usize) -> Vec3 {
-        let offset = self.followers.len() + 1 - self.snake_head.bodies.len();
-        if i > offset {
-            from_snake(self.snake_head.bodies[i - offset].position)
-        } else {
-            let mut pos = from_snake(self.snake_head.head_position());
-            match self.stack_state {
-                1 => {
-                    if i < offset {
-                        pos = from_snake(self.stack_pos);
-                        pos.y -= self.stack_time as f32 / 0.1 * RADIUS * 2.0;
-                    }
-                }
-                -1 => {
-                    pos.y += self.stack_time as f32 / 0.1 * RADIUS * 2.0;
-                }
-                _ => {}
+        self.formation
+            .entity_position(i, &self.snake_head, self.head_dir)
+    }
+
+    /// Gathered entities face where the head does.
+    fn entity_rotation(&self, i: usize) -> Quat {
+        match self.formation.walker_body(i) {
+            Some(body) => body_rotation(&self.snake_head.bodies[body]),
+            None => {
+                Transform::IDENTITY
+                    .looking_to(self.head_dir, Vec3::Y)
+                    .rotation
             }
-            pos.y += (offset - i) as f32 * RADIUS * 2.0;
-            pos
         }
     }
 }
@@ -143,172 +278,340 @@ impl Leader {
 pub struct MovementInput {
     pub ray: Option<Ray>,
     pub axis: Vec2,
+    /// Turn of the camera around the up axis, zero looking along -z. `axis` is relative to
+    /// it when set, x to the right of the camera and y where it looks.
+    pub camera_yaw: Option<f32>,
+    /// Sprint or slow down, for snakes with a `Locomotion`.
+    pub pace: Pace,
+    /// Queued by the next update on the `Player` snake.
+    pub formation: Option<FormationCommand>,
+}
+
+impl MovementInput {
+    /// `axis` on the ground, x along +x and y along -z turned by `camera_yaw`.
+    pub fn axis_direction(&self) -> Vec3 {
+        let v = Vec3::new(self.axis.x, 0.0, -self.axis.y);
+        match self.camera_yaw {
+            Some(yaw) => Quat::from_rotation_y(yaw) * v,
+            None => v,
+        }
+    }
+}
+
+/// Yaw of a camera with `rotation`, for `MovementInput::camera_yaw`. Looking straight down
+/// or up the top of the view counts as forward.
+pub fn camera_yaw(rotation: Quat) -> Option<f32> {
+    let forward = rotation * Vec3::NEG_Z;
+    let forward = if forward.xz().length_squared() > 1e-6 {
+        forward
+    } else {
+        rotation * Vec3::Y * -forward.y.signum()
+    };
+    forward.xz().try_normalize().map(|f| (-f.x).atan2(-f.y))
 }
 
-fn move_on_ground(from: Vec3, to: Vec3, ground: &GroundMesh) -> Vec3 {
+pub fn move_on_ground(from: Vec3, to: Vec3, layer: u32, ground: &Ground) -> (Vec3, u32) {
     let precision = 3.0;
     let mut v = to - from;
     let step = (v.length() / precision).floor() + 1.0;
     v /= step;
-    let mut p = from;
+    let mut p = (from, layer);
     for _ in 0..step as i32 {
-        p += v;
-        p = ground.fix_position(p, precision, RADIUS);
+        p = ground.fix_position(p.0 + v, precision, RADIUS, p.1);
     }
     p
 }
 
+/// Carry every snake standing on a moving `GroundCollider` along with it.
+fn ground_carry(
+    mut query_ground: Query<(&mut GroundCollider, &Transform)>,
+    mut query_leader: Query<&mut Leader>,
+) {
+    for (mut collider, tm) in query_ground.iter_mut() {
+        if let Some(delta) = collider.take_delta(tm) {
+            for mut leader in query_leader.iter_mut() {
+                leader.transform_layer(collider.layer, delta);
+            }
+        }
+    }
+}
+
 fn leader_move(
     time: Res<Time>,
-    input: Res<MovementInput>,
-    ground: Option<Res<GroundMesh>>,
-    mut query_leader: Query<&mut Leader>,
+    mut input: ResMut<MovementInput>,
+    ground: GroundParam,
+    mut query_leader: Query<(&mut Leader, Entity, Option<&SnakeInput>, Has<Player>)>,
     portal: Query<(&Portal, &Transform)>,
-    keyboard_input: Res<Input<KeyCode>>,
+    mut formation_events: EventWriter<FormationFinished>,
 ) {
     let delta_time = time.delta_seconds();
+    let formation = input.formation.take();
+    let axis_direction = input.axis_direction();
+    let pace = input.pace;
+    let ground = ground.get();
     let ground = ground.as_ref();
     let target = input.ray.map(|ray| {
         ground
             .and_then(|g| g.ray_cast(ray, 999999.0))
             .unwrap_or_else(|| ray.origin - ray.direction * (ray.origin.y / ray.direction.y))
     });
-    query_leader.par_iter_mut().for_each(|mut leader| {
-        if leader.can_move() {
-            if keyboard_input.pressed(KeyCode::U) {
-                leader.stack_up();
-            } else if keyboard_input.pressed(KeyCode::J) {
-                leader.stack_down();
+    query_leader
+        .par_iter_mut()
+        .for_each(|(mut leader, _, snake_input, is_player)| {
+            let (target, axis_direction, pace) = match snake_input {
+                Some(i) => (i.target, i.direction, i.pace),
+                None => (target, axis_direction, pace),
+            };
+            if let (true, Some(command)) = (is_player, formation) {
+                leader.formation.push(command);
             }
-        }
-        if leader.can_move() {
-            let mut leader_pos = from_snake(leader.snake_head.head_position());
-            let start_pos = leader_pos.clone();
-            let mut teleport = false;
-            for (pt, tm) in portal.iter() {
-                if tm.translation.distance_squared(leader_pos) < RADIUS * RADIUS {
-                    leader_pos = pt.0;
-                    teleport = true;
-                    break;
+            if leader.update_formation(delta_time as f64) {
+                let mut leader_pos = leader.snake_head.head_position();
+                let mut leader_layer = leader.snake_head.bodies[0].layer;
+                let start_pos = leader_pos;
+                let mut teleport = false;
+                for (pt, tm) in portal.iter() {
+                    if tm.translation.distance_squared(leader_pos) < RADIUS * RADIUS {
+                        leader_pos = pt.0;
+                        teleport = true;
+                        break;
+                    }
                 }
-            }
-            if !teleport {
-                let max_distance = delta_time * SPEED;
-                let move_delta = if let Some(p) = target {
-                    let mut v = p - leader_pos;
-                    v.y = 0.0;
-                    let len = v.length();
-                    if len > max_distance {
-                        v *= max_distance / len;
+                if let (false, Some(locomotion)) = (teleport, leader.locomotion) {
+                    leader_pos +=
+                        leader.locomote(locomotion, pace, target, axis_direction, delta_time);
+                } else if !teleport {
+                    let max_distance = delta_time * SPEED;
+                    let move_delta = if let Some(p) = target {
+                        let mut v = p - leader_pos;
+                        v.y = 0.0;
+                        let len = v.length();
+                        if len > max_distance {
+                            v *= max_distance / len;
+                        }
+                        v
+                    } else {
+                        axis_direction * max_distance
+                    };
+                    leader_pos += move_delta;
+                    if let Some(dir) = move_delta.try_normalize() {
+                        leader.head_dir = dir;
                     }
-                    v
-                } else {
-                    Vec3::new(input.axis.x, 0.0, -input.axis.y) * max_distance
-                };
-                leader_pos += move_delta;
-                if let Some(dir) = move_delta.try_normalize() {
-                    leader.head_dir = dir;
                 }
-            }
-            if let Some(g) = ground {
-                if !teleport {
-                    leader_pos = move_on_ground(start_pos, leader_pos, g);
-                    // leader_pos = character_move(tm.translation, leader_pos, RADIUS, &g.mesh, 1.5, false);
-                } else {
-                    leader_pos = g.fix_position(leader_pos, 3.0, RADIUS);
+                if let Some(g) = ground {
+                    if !teleport {
+                        (leader_pos, leader_layer) =
+                            move_on_ground(start_pos, leader_pos, leader_layer, g);
+                        // leader_pos = character_move(tm.translation, leader_pos, RADIUS, &g.mesh, 1.5, false);
+                    } else {
+                        (leader_pos, leader_layer) =
+                            g.fix_position(leader_pos, 3.0, RADIUS, leader_layer);
+                    }
+                }
+                if leader.block_head && !teleport {
+                    let (pos, contact) =
+                        leader.snake_head.block_head(start_pos, leader_pos, RADIUS);
+                    leader_pos = pos;
+                    leader.contacts.extend(contact);
                 }
-            }
-            leader.snake_head.move_head(
-                delta_time as f64,
-                to_snake(leader_pos),
                 if teleport {
-                    MoveMode::Teleport
-                } else {
-                    MoveMode::Normal
-                },
-            );
-        } else {
-            leader.update_stack(delta_time as f64);
+                    leader.speed = 0.0;
+                } else if delta_time > 0.0 {
+                    // the ground or the own body held the head back
+                    let moved = leader_pos.distance(start_pos) / delta_time;
+                    leader.speed = leader.speed.min(moved);
+                }
+                leader.snake_head.move_head(
+                    delta_time as f64,
+                    leader_pos,
+                    leader_layer,
+                    if teleport {
+                        MoveMode::Teleport
+                    } else {
+                        MoveMode::Normal
+                    },
+                );
+            }
+        });
+    for (mut leader, entity, _, _) in query_leader.iter_mut() {
+        for (command, rejected) in leader.formation.take_done() {
+            formation_events.send(FormationFinished {
+                leader: entity,
+                command,
+                rejected,
+            });
         }
-    });
+    }
 }
 
 fn body_move(
     time: Res<Time>,
-    ground: Option<Res<GroundMesh>>,
+    ground: GroundParam,
     mut query_leader: Query<(&mut Leader, Entity)>,
-    mut query_tm: Query<&mut Transform>,
+    mut query_tm: Query<&mut Transform, Without<GroundCollider>>,
+    mut hit_events: EventWriter<SnakeHitEvent>,
 ) {
     let delta_time = time.delta_seconds();
+    let ground = ground.get();
     // let delta_time = 1.0 / 60.0;
     query_leader.par_iter_mut().for_each(|(mut leader, _)| {
         let leader = &mut *leader;
         let fix_position = ground.as_ref().map(|g| {
-            |_body: &SnakeBody, pos, prev| {
-                to_snake(move_on_ground(from_snake(prev), from_snake(pos), g))
+            |body: &SnakeBody<YUp>, pos, prev| {
+                let (p, layer) = move_on_ground(prev, pos, body.layer, g);
+                (p, layer)
             }
         });
+        let speed = leader.speed_limit();
         leader.snake_head.update_body(RADIUS);
-        leader.snake_head.solve_body(
-            delta_time * SPEED,
-            delta_time * SPEED * 0.1,
+        let contacts = leader.snake_head.solve_body(
+            delta_time * speed,
+            delta_time * speed * 0.1,
             RADIUS,
             fix_position,
         );
+        leader.contacts.extend(contacts);
+        leader.stuck.clear();
         if let Some(g) = ground.as_ref() {
-            for body in leader.snake_head.bodies.iter_mut().skip(1) {
-                let mut pos = from_snake(body.position);
-                if (pos.y - body.target.y).abs() > RADIUS * 2.0 {
+            for (i, body) in leader.snake_head.bodies.iter_mut().enumerate().skip(1) {
+                let mut pos = body.position;
+                if body.layer != body.target_layer {
                     // fix different layer
                     let p0 = Vec2::new(pos.x, pos.z);
-                    let t1 = from_snake(body.target);
+                    let t1 = body.target;
                     let p1 = Vec2::new(t1.x, t1.z);
                     if p0.distance_squared(p1) < RADIUS * RADIUS {
                         let ray = Ray {
-                            origin: Vec3::new(pos.x, t1.y - RADIUS * 0.5, pos.z),
+                            origin: Vec3::new(pos.x, t1.y, pos.z),
                             direction: Vec3::new(0.0, -1.0, 0.0),
                         };
-                        if let Some(p) = g.ray_cast(ray, RADIUS) {
+                        if let Some(p) = g.ray_cast_layer(ray, RADIUS * 2.0, body.target_layer) {
                             pos = p;
                             pos.y += RADIUS;
+                            body.layer = body.target_layer;
                         }
                     }
                 } else {
                     // fix stuck
-                    let target = from_snake(body.target);
+                    let target = body.target;
                     let v = target - pos;
                     let len2 = v.length_squared();
                     if len2 > RADIUS * RADIUS * 64.0 {
                         let pos1 = pos + v * (3.0 / len2.sqrt());
-                        let pos2 = g.fix_position(pos1, 3.0, RADIUS);
+                        let (pos2, _) = g.fix_position(pos1, 3.0, RADIUS, body.layer);
                         if pos2.distance_squared(pos) < 0.1 {
                             pos = target;
+                            leader.stuck.push(i);
                         }
                     }
                 }
-                body.position = to_snake(pos);
+                body.position = pos;
             }
         }
+        let normal = ground.as_ref().map(|g| {
+            |body: &SnakeBody<YUp>| g.normal(body.position, RADIUS, RADIUS).unwrap_or(Vec3::Y)
+        });
+        leader.snake_head.update_frames(RADIUS, normal);
     });
-    for (leader, entity) in query_leader.iter() {
+    for (mut leader, entity) in query_leader.iter_mut() {
+        for contact in leader.contacts.drain(..) {
+            hit_events.send(SnakeHitEvent {
+                leader: entity,
+                other: entity,
+                body: contact.body,
+                point: contact.point,
+                relative_speed: contact.relative_speed,
+            });
+        }
         let iter_entity = iter::once(&entity).chain(leader.followers.iter());
         let mut iter_tm = query_tm.iter_many_mut(iter_entity);
         let mut i = 0;
         while let Some(mut tm) = iter_tm.fetch_next() {
             tm.translation = leader.entity_position(i);
+            tm.rotation = leader.entity_rotation(i);
             i += 1;
         }
     }
 }
 
-fn setup_logic(mut commands: Commands) {
-    let snake_bodies: Vec<_> = (0..10)
+/// Rotation of a body facing `forward`, identity before the first `update_frames`.
+pub fn body_rotation(body: &SnakeBody<YUp>) -> Quat {
+    if body.forward == Vec3::ZERO {
+        return Quat::IDENTITY;
+    }
+    Transform::IDENTITY
+        .looking_to(body.forward, body.up)
+        .rotation
+}
+
+fn snake_hit(query_leader: Query<(&Leader, Entity)>, mut hit_events: EventWriter<SnakeHitEvent>) {
+    for [(leader0, entity0), (leader1, entity1)] in query_leader.iter_combinations() {
+        for (head, leader, other, other_leader) in [
+            (&leader0.snake_head, entity0, entity1, &leader1.snake_head),
+            (&leader1.snake_head, entity1, entity0, &leader0.snake_head),
+        ] {
+            for contact in head.contacts_with(other_leader, RADIUS) {
+                hit_events.send(SnakeHitEvent {
+                    leader,
+                    other,
+                    body: contact.body,
+                    point: contact.point,
+                    relative_speed: contact.relative_speed,
+                });
+            }
+        }
+    }
+}
+
+fn pickup_collect(
+    mut commands: Commands,
+    query_pickup: Query<(Entity, &Transform), With<Pickup>>,
+    mut query_leader: Query<(Entity, &mut Leader)>,
+    mut pickup_events: EventWriter<PickupEvent>,
+    mut grow_events: EventWriter<GrowEvent>,
+) {
+    for (pickup, tm) in query_pickup.iter() {
+        for (leader_entity, mut leader) in query_leader.iter_mut() {
+            let head_pos = leader.snake_head.head_position();
+            if head_pos.distance_squared(tm.translation) < RADIUS * RADIUS * 4.0 {
+                commands.entity(pickup).despawn();
+                let follower = commands.spawn_empty().id();
+                leader.grow(follower);
+                let tail_pos = leader.snake_head.bodies.last().unwrap().position;
+                commands
+                    .entity(follower)
+                    .insert(Transform::from_translation(tail_pos));
+                pickup_events.send(PickupEvent {
+                    leader: leader_entity,
+                    pickup,
+                });
+                grow_events.send(GrowEvent {
+                    leader: leader_entity,
+                    follower,
+                });
+                break;
+            }
+        }
+    }
+}
+
+/// Spawn a snake of `count` bodies with the head at `position` and the followers lined up
+/// behind it along -x, the head moving under `locomotion` if any. Returns the leader entity.
+pub fn spawn_snake(
+    commands: &mut Commands,
+    position: Vec3,
+    count: usize,
+    locomotion: Option<Locomotion>,
+) -> Entity {
+    let snake_bodies: Vec<_> = (0..count.max(1))
         .map(|i| {
-            SnakeBody::new(
+            let mut body = SnakeBody::new(
                 get_delay(i),
                 get_distance(i),
-                Vec3::new(-get_distance(i), 0.0, RADIUS),
-            )
+                position - Vec3::X * get_distance(i),
+            );
+            body.follow = FOLLOW_MODE;
+            body
         })
         .collect();
     let followers: Vec<_> = snake_bodies
@@ -316,26 +619,52 @@ fn setup_logic(mut commands: Commands) {
         .skip(1)
         .map(|body| {
             commands
-                .spawn(Transform::from_translation(from_snake(body.position)))
+                .spawn(Transform::from_translation(body.position))
                 .id()
         })
         .collect();
-    let head_pos = from_snake(snake_bodies[0].position);
-    commands.spawn((
-        Transform::from_translation(head_pos),
-        Leader::new(snake_bodies, followers),
-    ));
-    let portals = [
-        (0.0, 200.0, 0.0, -200.0),
-        (150.0, 180.0, -150.0, -180.0),
-        (0.0, -210.0, 200.0, 0.0),
-    ];
-    for p in portals.iter() {
+    let head_pos = snake_bodies[0].position;
+    let mut leader = Leader::new(snake_bodies, followers);
+    leader.locomotion = locomotion;
+    commands
+        .spawn((Transform::from_translation(head_pos), leader))
+        .id()
+}
+
+fn setup_logic(mut commands: Commands, level: Res<Level>) {
+    let player = spawn_snake(&mut commands, level.spawn, 10, None);
+    commands.entity(player).insert(Player);
+    for (i, (position, waypoints)) in level.snakes.iter().enumerate() {
+        // `SnakeAi` picks the pace
+        let snake = spawn_snake(&mut commands, *position, 6, Some(Locomotion::default()));
+        commands.entity(snake).insert((
+            SnakeInput::default(),
+            SnakeAi::new(waypoints.clone(), i as u32),
+        ));
+    }
+    for (i, &(entrance, exit)) in level.portals.iter().enumerate() {
         commands.spawn((
-            Transform::from_translation(from_snake(Vec3::new(p.0, p.1, RADIUS))),
-            Portal(from_snake(Vec3::new(p.2, p.3, RADIUS))),
+            Transform::from_translation(entrance),
+            Portal(exit),
+            LevelIndex(i),
         ));
     }
+    for (i, &p) in level.pickups.iter().enumerate() {
+        commands.spawn((Transform::from_translation(p), Pickup, LevelIndex(i)));
+    }
+}
+
+/// The movement systems, in the order they run.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SnakeSystem {
+    GroundCarry,
+    AiSteer,
+    LeaderMove,
+    BodyMove,
+    SnakeHit,
+    PickupCollect,
+    CollectMetrics,
+    UpdateSkeletons,
 }
 
 pub struct SnakeLogicPlugin;
@@ -343,7 +672,27 @@ pub struct SnakeLogicPlugin;
 impl Plugin for SnakeLogicPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MovementInput>()
+            .init_resource::<Level>()
+            .add_event::<PickupEvent>()
+            .add_event::<GrowEvent>()
+            .add_event::<SnakeHitEvent>()
+            .add_event::<FormationFinished>()
             .add_systems(Startup, setup_logic)
-            .add_systems(Update, (leader_move, body_move.after(leader_move)));
+            .add_systems(
+                Update,
+                (
+                    ground_carry.in_set(SnakeSystem::GroundCarry),
+                    ai_steer.in_set(SnakeSystem::AiSteer),
+                    leader_move.in_set(SnakeSystem::LeaderMove),
+                    body_move.in_set(SnakeSystem::BodyMove),
+                    snake_hit.in_set(SnakeSystem::SnakeHit),
+                    pickup_collect.in_set(SnakeSystem::PickupCollect),
+                    collect_metrics
+                        .run_if(resource_exists::<Metrics>())
+                        .in_set(SnakeSystem::CollectMetrics),
+                    update_skeletons.in_set(SnakeSystem::UpdateSkeletons),
+                )
+                    .chain(),
+            );
     }
 }
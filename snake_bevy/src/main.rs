@@ -2,98 +2,193 @@ use bevy::asset::io::file::FileAssetReader;
 use bevy::pbr::NotShadowCaster;
 use bevy::prelude::*;
 use bevy::window;
-#[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
 use std::fs;
 
-use snake_move::*;
-
-// mod character_move;
-mod ground_mesh;
+mod bindings;
+mod camera;
+mod editor;
 mod lines;
-mod logic;
+mod tube;
 
-use ground_mesh::GroundMesh;
-use logic::*;
+use bindings::{parse_bindings, Action, ActionInput};
+use camera::CameraController;
+use snake_bevy::formation::{FormationCommand, FormationShape};
+use snake_bevy::ground_mesh::GroundMesh;
+use snake_bevy::level::parse_level;
+use snake_bevy::logic::*;
+#[cfg(feature = "serde")]
+use snake_bevy::save::{read_save, write_save};
+
+/// Level loaded at start and written by the editor, relative to the asset base path.
+const LEVEL_PATH: &str = "assets/level.txt";
+/// Key and gamepad bindings, relative to the asset base path.
+const BINDINGS_PATH: &str = "assets/bindings.txt";
 
 fn movement_input(
     mut movement_input: ResMut<MovementInput>,
     window: Query<&Window>,
     camera: Query<(&Camera, &GlobalTransform)>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: ActionInput,
     mousebutton_input: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    mut shape: Local<FormationShape>,
 ) {
-    movement_input.axis = Vec2::new(
-        keyboard_input.pressed(KeyCode::D) as i32 as f32
-            - keyboard_input.pressed(KeyCode::A) as i32 as f32,
-        keyboard_input.pressed(KeyCode::W) as i32 as f32
-            - keyboard_input.pressed(KeyCode::S) as i32 as f32,
+    let (camera, camera_transform) = camera.single();
+    let keys = Vec2::new(
+        actions.pressed(Action::Right) as i32 as f32 - actions.pressed(Action::Left) as i32 as f32,
+        actions.pressed(Action::Up) as i32 as f32 - actions.pressed(Action::Down) as i32 as f32,
     )
     .normalize_or_zero();
-    movement_input.ray = if mousebutton_input.pressed(MouseButton::Left) {
-        let (camera, camera_transform) = camera.single();
-        let wnd = window.single();
-        wnd.cursor_position()
-            .and_then(|pos| camera.viewport_to_world(camera_transform, pos))
+    movement_input.axis = (keys + actions.stick()).clamp_length_max(1.0);
+    movement_input.pace = if actions.pressed(Action::Sprint) {
+        Pace::Sprint
+    } else if actions.pressed(Action::Slow) {
+        Pace::Slow
+    } else {
+        Pace::Normal
+    };
+    // walk relative to where the camera looks
+    movement_input.camera_yaw = camera_yaw(camera_transform.compute_transform().rotation);
+    movement_input.formation = if actions.just_pressed(Action::StackUp) {
+        Some(FormationCommand::StackUp(0))
+    } else if actions.just_pressed(Action::StackDown) {
+        Some(FormationCommand::StackDown(0))
+    } else if actions.just_pressed(Action::Formation) {
+        *shape = shape.next();
+        Some(FormationCommand::Shape(*shape))
     } else {
         None
     };
-}
-
-#[cfg(feature = "serde")]
-#[derive(Serialize, Deserialize)]
-struct SaveData {
-    snake_head: SnakeHead,
+    // a finger on the screen moves like the pressed mouse
+    let pointer = if let Some(touch) = touches.iter().next() {
+        Some(touch.position())
+    } else if mousebutton_input.pressed(MouseButton::Left) {
+        window.single().cursor_position()
+    } else {
+        None
+    };
+    movement_input.ray = pointer.and_then(|pos| camera.viewport_to_world(camera_transform, pos));
 }
 
 #[cfg(feature = "serde")]
 fn save_load(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query_leader: Query<(&mut Leader, &mut Transform)>,
+    actions: ActionInput,
+    mut query_leader: Query<(&mut Leader, &mut Transform), With<Player>>,
     mut query_tm: Query<&mut Transform, Without<Leader>>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Z) {
+    if actions.just_pressed(Action::Save) {
         let (leader, _) = query_leader.single();
-        let data = SaveData {
-            snake_head: leader.snake_head.clone(),
-        };
-        let serialized = serde_json::to_string(&data).unwrap();
-        fs::write("save.json", serialized).unwrap();
-    } else if keyboard_input.just_pressed(KeyCode::X) {
+        fs::write("save.json", write_save(&leader.snake_head)).unwrap();
+    } else if actions.just_pressed(Action::Load) {
         if let Ok(s) = fs::read_to_string("save.json") {
-            if let Ok(data) = serde_json::from_str::<SaveData>(&s) {
+            if let Ok(snake_head) = read_save(&s) {
                 let (mut leader, mut leader_tm) = query_leader.single_mut();
-                leader.snake_head = data.snake_head;
-                leader_tm.translation = from_snake(leader.snake_head.bodies[0].position);
+                leader.snake_head = snake_head;
+                leader_tm.translation = leader.snake_head.bodies[0].position;
                 let mut iter_follower_tm = query_tm.iter_many_mut(&leader.followers);
                 let mut iter_body = leader.snake_head.bodies.iter().skip(1);
                 while let (Some(mut tm), Some(body)) =
                     (iter_follower_tm.fetch_next(), iter_body.next())
                 {
-                    tm.translation = from_snake(body.position);
+                    tm.translation = body.position;
                 }
             }
         }
     }
 }
 
+/// The demo snakes speed up and arc into turns.
+fn setup_locomotion(mut query_leader: Query<&mut Leader>) {
+    for mut leader in query_leader.iter_mut() {
+        leader.locomotion = Some(Locomotion::default());
+    }
+}
+
+#[derive(Resource)]
+struct BodyMesh(Handle<Mesh>);
+
+#[derive(Resource)]
+struct PickupRender {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Resource)]
+struct PortalMesh(Handle<Mesh>);
+
+/// Cylinder showing the exit of the portal entity.
+#[derive(Component)]
+struct PortalExit(Entity);
+
+fn grow_render(
+    mut commands: Commands,
+    mut grow_events: EventReader<GrowEvent>,
+    body_mesh: Res<BodyMesh>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query_leader: Query<&Leader>,
+    query_tm: Query<&Transform>,
+) {
+    for event in grow_events.read() {
+        if let (Ok(leader), Ok(tm)) = (query_leader.get(event.leader), query_tm.get(event.follower))
+        {
+            commands.entity(event.follower).insert(PbrBundle {
+                mesh: body_mesh.0.clone(),
+                material: materials.add(StandardMaterial::from(color(leader.followers.len()))),
+                transform: *tm,
+                ..default()
+            });
+        }
+    }
+}
+
 fn color(i: usize) -> Color {
-    let l = if i % 2 == 0 { 0.5 } else { 0.4 };
+    let l = [0.5, 0.4][i % 2];
     Color::hsl(i as f32 * 36.0, 1.0, l)
 }
 
+/// Cylinders at the entrance `portal_entity` standing at `tm` and at its `exit`.
+fn render_portal(
+    commands: &mut Commands,
+    portal_entity: Entity,
+    tm: Transform,
+    exit: Vec3,
+    mesh: &Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+) {
+    commands.entity(portal_entity).insert((
+        PbrBundle {
+            mesh: mesh.clone(),
+            material: material.clone(),
+            transform: tm,
+            ..default()
+        },
+        NotShadowCaster,
+    ));
+    commands.spawn((
+        PbrBundle {
+            mesh: mesh.clone(),
+            material,
+            transform: Transform::from_translation(exit),
+            ..default()
+        },
+        NotShadowCaster,
+        PortalExit(portal_entity),
+    ));
+}
+
+fn portal_color(i: usize) -> Color {
+    Color::hsla(i as f32 * 49.0 + 180.0, 1.0, 0.4, 0.4)
+}
+
 fn setup_render(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut clear_color: ResMut<ClearColor>,
     query_leader: Query<(&Leader, Entity)>,
     query_portal: Query<(&Portal, Entity)>,
     query_tm: Query<&Transform>,
 ) {
-    clear_color.0 = Color::BLACK;
-
     let sphere = meshes.add(
         shape::Icosphere {
             radius: RADIUS,
@@ -118,6 +213,7 @@ fn setup_render(
             });
         }
     }
+    commands.insert_resource(BodyMesh(sphere.clone()));
     let cylinder = meshes.add(
         shape::Cylinder {
             radius: RADIUS,
@@ -128,26 +224,16 @@ fn setup_render(
         .into(),
     );
     for (i, (portal, portal_entity)) in query_portal.iter().enumerate() {
-        let pcolor = Color::hsla(i as f32 * 49.0 + 180.0, 1.0, 0.4, 0.4);
-        commands.entity(portal_entity).insert((
-            PbrBundle {
-                mesh: cylinder.clone(),
-                material: materials.add(StandardMaterial::from(pcolor)),
-                transform: *query_tm.get(portal_entity).unwrap(),
-                ..default()
-            },
-            NotShadowCaster,
-        ));
-        commands.spawn((
-            PbrBundle {
-                mesh: cylinder.clone(),
-                material: materials.add(StandardMaterial::from(pcolor)),
-                transform: Transform::from_translation(portal.0),
-                ..default()
-            },
-            NotShadowCaster,
-        ));
+        render_portal(
+            &mut commands,
+            portal_entity,
+            *query_tm.get(portal_entity).unwrap(),
+            portal.0,
+            &cylinder,
+            materials.add(StandardMaterial::from(portal_color(i))),
+        );
     }
+    commands.insert_resource(PortalMesh(cylinder));
     commands.spawn(SceneBundle {
         scene: asset_server.load("ground.glb#Scene0"),
         ..default()
@@ -161,9 +247,41 @@ fn setup_render(
         transform: Transform::from_rotation(Quat::from_euler(EulerRot::YXZ, -1.5, -1.5, 0.0)),
         ..default()
     });
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(0.0, 750.0, 200.0).looking_at(Vec3::ZERO, Vec3::Y),
-        ..default()
+    commands.spawn((
+        Camera3dBundle {
+            transform: Transform::from_xyz(0.0, 750.0, 200.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        },
+        CameraController::looking_at(Vec3::new(0.0, 750.0, 200.0), Vec3::ZERO),
+    ));
+}
+
+fn setup_pickup_render(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query_pickup: Query<(Entity, &Transform), With<Pickup>>,
+) {
+    let pickup_sphere = meshes.add(
+        shape::Icosphere {
+            radius: RADIUS * 0.5,
+            subdivisions: 2,
+        }
+        .try_into()
+        .unwrap(),
+    );
+    let pickup_material = materials.add(StandardMaterial::from(Color::GOLD));
+    for (pickup_entity, tm) in query_pickup.iter() {
+        commands.entity(pickup_entity).insert(PbrBundle {
+            mesh: pickup_sphere.clone(),
+            material: pickup_material.clone(),
+            transform: *tm,
+            ..default()
+        });
+    }
+    commands.insert_resource(PickupRender {
+        mesh: pickup_sphere,
+        material: pickup_material,
     });
 }
 
@@ -171,9 +289,14 @@ pub struct SnakePlugin;
 
 impl Plugin for SnakePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PostStartup, setup_render)
+        app.insert_resource(ClearColor(Color::BLACK))
+            .add_systems(
+                PostStartup,
+                (setup_render, setup_pickup_render, setup_locomotion),
+            )
             .add_systems(PreUpdate, movement_input)
-            .add_systems(Update, window::close_on_esc);
+            .add_systems(Update, window::close_on_esc)
+            .add_systems(PostUpdate, grow_render);
         #[cfg(feature = "serde")]
         app.add_systems(Update, save_load);
 
@@ -182,6 +305,27 @@ impl Plugin for SnakePlugin {
         if let Some(ground) = ground_data.and_then(|data| GroundMesh::from_obj(&data)) {
             app.insert_resource(ground);
         }
+
+        let bindings_path = FileAssetReader::get_base_path().join(BINDINGS_PATH);
+        let bindings = match fs::read_to_string(&bindings_path).map(|data| parse_bindings(&data)) {
+            Ok(Ok(bindings)) => bindings,
+            Ok(Err(e)) => {
+                warn!("{}: {}", bindings_path.display(), e);
+                default()
+            }
+            Err(_) => default(),
+        };
+        app.insert_resource(bindings);
+
+        let level_path = FileAssetReader::get_base_path().join(LEVEL_PATH);
+        if let Ok(data) = fs::read_to_string(&level_path) {
+            match parse_level(&data) {
+                Ok(level) => {
+                    app.insert_resource(level);
+                }
+                Err(e) => warn!("{}: {}", level_path.display(), e),
+            }
+        }
     }
 }
 
@@ -192,6 +336,9 @@ fn main() {
             SnakeLogicPlugin,
             SnakePlugin,
             lines::LinesPlugin,
+            tube::TubePlugin,
+            editor::EditorPlugin,
+            camera::CameraPlugin,
         ))
         .run();
 }
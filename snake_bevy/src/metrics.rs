@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use snake_move::*;
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use super::logic::{Leader, Player, RADIUS};
+
+/// A body moving farther than this in one frame went through a portal.
+const JUMP: f32 = RADIUS * 4.0;
+
+/// Movement quality of one walking body over a run, in snake units and seconds.
+#[derive(Clone, Default, Debug)]
+pub struct BodyMetrics {
+    pub frames: u32,
+    /// Distance from the head trail, once the target of the body is on it.
+    pub max_deviation: f32,
+    pub sum_deviation: f32,
+    pub deviation_frames: u32,
+    /// Depth of the deepest overlap with another colliding body.
+    pub max_overlap: f32,
+    /// Second derivative of the position.
+    pub max_jitter: f32,
+    pub sum_jitter: f32,
+    pub jitter_frames: u32,
+    /// Frames the "fix stuck" branch of `body_move` put the body on its target.
+    pub stuck_frames: u32,
+    /// Seconds from the head going through a portal until this body does.
+    pub teleport_delays: Vec<f32>,
+    /// Position of the last two frames.
+    history: [Option<Vec3>; 2],
+    /// Times the head went through a portal this body didn't go through yet.
+    pending: VecDeque<f32>,
+}
+
+impl BodyMetrics {
+    fn reset_history(&mut self) {
+        self.history = [None, None];
+    }
+}
+
+/// Collects movement metrics of the first snake while this resource exists.
+#[derive(Resource, Default)]
+pub struct Metrics {
+    pub time: f32,
+    pub bodies: Vec<BodyMetrics>,
+    /// Path records of the head, now and the most there were.
+    pub records: usize,
+    pub max_records: usize,
+}
+
+fn trail_distance(snake_head: &SnakeHead<YUp>, p: Vec3) -> Option<f32> {
+    let records: Vec<_> = snake_head.get_path_records().collect();
+    records
+        .windows(2)
+        .filter(|w| w[1].1 > w[0].1 || w[0].2 == w[1].2)
+        .map(|w| {
+            let (a, b) = (w[0].2, w[1].2);
+            let ab = b - a;
+            let t = ((p - a).dot(ab) / ab.length_squared().max(1e-6)).clamp(0.0, 1.0);
+            (a + ab * t).distance(p)
+        })
+        .reduce(f32::min)
+        .or_else(|| records.first().map(|r| r.2.distance(p)))
+}
+
+impl Metrics {
+    fn update(&mut self, leader: &Leader, delta_time: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+        self.time += delta_time;
+        let snake_head = &leader.snake_head;
+        self.records = snake_head.record_count();
+        self.max_records = self.max_records.max(self.records);
+        let bodies = &snake_head.bodies;
+        if self.bodies.len() != bodies.len() {
+            // the chain changed, positions of the last frames belong to other bodies
+            self.bodies.resize_with(bodies.len(), Default::default);
+            for m in self.bodies.iter_mut() {
+                m.reset_history();
+                m.pending.clear();
+            }
+        }
+        let first_distance = snake_head.get_path_records().next().map(|r| r.1);
+        let mut head_teleport = false;
+        for (i, body) in bodies.iter().enumerate() {
+            let stuck = leader.stuck_bodies().contains(&i);
+            let m = &mut self.bodies[i];
+            m.frames += 1;
+            if stuck {
+                m.stuck_frames += 1;
+            }
+
+            let pos = body.position;
+            let jumped = m.history[0].is_some_and(|p1| p1.distance(pos) > JUMP);
+            if stuck || jumped {
+                if jumped && !stuck {
+                    if i == 0 {
+                        head_teleport = true;
+                    } else if let Some(t) = m.pending.pop_front() {
+                        m.teleport_delays.push(self.time - t);
+                    }
+                }
+                m.history = [Some(pos), None];
+            } else {
+                if let [Some(p1), Some(p2)] = m.history {
+                    let jitter = (pos - p1 * 2.0 + p2).length() / (delta_time * delta_time);
+                    m.max_jitter = m.max_jitter.max(jitter);
+                    m.sum_jitter += jitter;
+                    m.jitter_frames += 1;
+                }
+                m.history = [Some(pos), m.history[0]];
+            }
+
+            // a target across a portal isn't reached by walking along the trail
+            let on_trail = first_distance.is_some_and(|d| body.move_distance() >= d)
+                && pos.distance(body.target) < JUMP;
+            if i > 0 && on_trail {
+                if let Some(d) = trail_distance(snake_head, pos) {
+                    m.max_deviation = m.max_deviation.max(d);
+                    m.sum_deviation += d;
+                    m.deviation_frames += 1;
+                }
+            }
+
+            // bodies stepping out of a stack start inside the one they leave, not colliding
+            for (j, other) in bodies.iter().enumerate() {
+                if i != j && body.collision && other.collision && body.layer == other.layer {
+                    let overlap = RADIUS * 2.0 - pos.distance(other.position);
+                    m.max_overlap = m.max_overlap.max(overlap);
+                }
+            }
+        }
+        if head_teleport {
+            for m in self.bodies.iter_mut().skip(1) {
+                m.pending.push_back(self.time);
+            }
+        }
+    }
+
+    /// One line per body, values rounded to 0.1, then the path record counts.
+    pub fn report(&self) -> String {
+        let mean = |sum: f32, n: u32| if n > 0 { sum / n as f32 } else { 0.0 };
+        let mut s = String::from(
+            "body frames deviation_max deviation_mean overlap_max jitter_max jitter_mean \
+             stuck_frames teleports teleport_delay_max\n",
+        );
+        for (i, m) in self.bodies.iter().enumerate() {
+            let delay_max = m.teleport_delays.iter().copied().fold(0.0, f32::max);
+            writeln!(
+                s,
+                "{} {} {:.1} {:.1} {:.1} {:.1} {:.1} {} {} {:.1}",
+                i,
+                m.frames,
+                m.max_deviation,
+                mean(m.sum_deviation, m.deviation_frames),
+                m.max_overlap.max(0.0),
+                m.max_jitter,
+                mean(m.sum_jitter, m.jitter_frames),
+                m.stuck_frames,
+                m.teleport_delays.len(),
+                delay_max,
+            )
+            .unwrap();
+        }
+        writeln!(
+            s,
+            "records {} records_max {}",
+            self.records, self.max_records
+        )
+        .unwrap();
+        s
+    }
+}
+
+pub fn collect_metrics(
+    time: Res<Time>,
+    mut metrics: ResMut<Metrics>,
+    query_leader: Query<&Leader, With<Player>>,
+) {
+    if let Some(leader) = query_leader.iter().next() {
+        metrics.update(leader, time.delta_seconds());
+    }
+}
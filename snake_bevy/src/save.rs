@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use snake_move::{SnakeHead, YUp, ZUp};
+
+/// Version of the saves `write_save` writes. Saves without one are from before the snake
+/// moved in the axes of bevy, with the head path in `ZUp`.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SaveData<S: snake_move::Space> {
+    #[serde(default)]
+    version: u32,
+    snake_head: SnakeHead<S>,
+}
+
+#[derive(Deserialize)]
+struct SaveVersion {
+    #[serde(default)]
+    version: u32,
+}
+
+/// The save file of the player snake.
+pub fn write_save(snake_head: &SnakeHead<YUp>) -> String {
+    serde_json::to_string(&SaveData {
+        version: SAVE_VERSION,
+        snake_head: snake_head.clone(),
+    })
+    .unwrap()
+}
+
+/// Read a save written by `write_save`, or an unversioned one converted from `ZUp`.
+pub fn read_save(data: &str) -> Result<SnakeHead<YUp>, String> {
+    let version = serde_json::from_str::<SaveVersion>(data)
+        .map_err(|e| e.to_string())?
+        .version;
+    match version {
+        0 => serde_json::from_str::<SaveData<ZUp>>(data)
+            .map(|save| save.snake_head.into_space())
+            .map_err(|e| e.to_string()),
+        SAVE_VERSION => serde_json::from_str::<SaveData<YUp>>(data)
+            .map(|save| save.snake_head)
+            .map_err(|e| e.to_string()),
+        _ => Err(format!(
+            "save version {} is newer than {}",
+            version, SAVE_VERSION
+        )),
+    }
+}
@@ -0,0 +1,43 @@
+/// One line of a scenario script, applied from `frame` on.
+#[derive(Clone, Copy)]
+pub enum Command {
+    Axis(f32, f32),
+    Target(f32, f32),
+    Release,
+    Stack(bool),
+    Formation(u32),
+}
+
+/// Parse a script with one `frame command args` per line, sorted by frame.
+pub fn parse_script(data: &str) -> Result<Vec<(u32, Command)>, String> {
+    let mut commands = Vec::new();
+    for (line_no, line) in data.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = || format!("line {}: can't parse `{}`", line_no + 1, line);
+        let t: Vec<_> = line.split_whitespace().collect();
+        let num = |i: usize| {
+            t.get(i)
+                .and_then(|s| s.parse::<f32>().ok())
+                .ok_or_else(error)
+        };
+        let frame = t[0].parse::<u32>().map_err(|_| error())?;
+        let command = match t.get(1).copied() {
+            Some("axis") => Command::Axis(num(2)?, num(3)?),
+            Some("target") => Command::Target(num(2)?, num(3)?),
+            Some("release") => Command::Release,
+            Some("stack") => match t.get(2).copied() {
+                Some("up") => Command::Stack(true),
+                Some("down") => Command::Stack(false),
+                _ => return Err(error()),
+            },
+            Some("formation") => Command::Formation(num(2)? as u32),
+            _ => return Err(error()),
+        };
+        commands.push((frame, command));
+    }
+    commands.sort_by_key(|c| c.0);
+    Ok(commands)
+}
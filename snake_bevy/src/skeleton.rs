@@ -0,0 +1,125 @@
+use bevy::math::Affine3A;
+use bevy::prelude::*;
+
+use super::logic::Leader;
+
+/// Curve points between two bodies the bones are placed on.
+const CURVE_STEPS: usize = 8;
+
+/// Drives the joints of a rigged snake model with the walking bodies of `leader`, the first
+/// joint at the head and the last at the tail. Members gathered in a formation are left out,
+/// the model keeps to the path of the snake.
+#[derive(Component)]
+pub struct SnakeSkeleton {
+    pub leader: Entity,
+    pub joints: Vec<Entity>,
+    /// Every joint is a child of the one before, its transform is written relative to it.
+    /// Otherwise the joints are siblings of the first, relative to the same parent.
+    pub chained: bool,
+}
+
+/// The head and the walking bodies of `leader` in order, on the path of the head.
+pub fn chain_points(leader: &Leader) -> Vec<Vec3> {
+    leader
+        .snake_head
+        .bodies
+        .iter()
+        .map(|body| body.position)
+        .collect()
+}
+
+pub fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    ((p1 * 2.0)
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+/// `count` bones evenly spaced along a curve through `points`, facing toward the first
+/// point. Up starts at `up` and is carried along the curve so the bones don't twist.
+pub fn resample_bones(points: &[Vec3], count: usize, up: Vec3) -> Vec<Transform> {
+    if points.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let mut curve = vec![points[0]];
+    for i in 0..points.len() - 1 {
+        let p0 = points[i.saturating_sub(1)];
+        let p3 = points[(i + 2).min(points.len() - 1)];
+        for k in 1..=CURVE_STEPS {
+            let t = k as f32 / CURVE_STEPS as f32;
+            curve.push(catmull_rom(p0, points[i], points[i + 1], p3, t));
+        }
+    }
+    let mut lengths = vec![0.0];
+    for w in curve.windows(2) {
+        lengths.push(lengths.last().unwrap() + w[0].distance(w[1]));
+    }
+    let total = *lengths.last().unwrap();
+
+    let mut bones = Vec::with_capacity(count);
+    let mut up = up;
+    let mut forward = Vec3::ZERO;
+    for k in 0..count {
+        let s = if count > 1 {
+            total * k as f32 / (count - 1) as f32
+        } else {
+            0.0
+        };
+        let position = if curve.len() > 1 {
+            let i = lengths
+                .partition_point(|&l| l < s)
+                .clamp(1, curve.len() - 1);
+            let (l0, l1) = (lengths[i - 1], lengths[i]);
+            let t = if l1 > l0 { (s - l0) / (l1 - l0) } else { 0.0 };
+            forward = (curve[i - 1] - curve[i]).try_normalize().unwrap_or(forward);
+            curve[i - 1].lerp(curve[i], t)
+        } else {
+            curve[0]
+        };
+        if forward == Vec3::ZERO {
+            bones.push(Transform::from_translation(position));
+            continue;
+        }
+        up = (up - forward * up.dot(forward))
+            .try_normalize()
+            .unwrap_or_else(|| forward.any_orthonormal_vector());
+        bones.push(Transform::from_translation(position).looking_to(forward, up));
+    }
+    bones
+}
+
+pub fn update_skeletons(
+    query_skeleton: Query<&SnakeSkeleton>,
+    query_leader: Query<&Leader>,
+    query_parent: Query<&Parent>,
+    query_global: Query<&GlobalTransform>,
+    mut query_tm: Query<&mut Transform>,
+) {
+    for skeleton in query_skeleton.iter() {
+        let Ok(leader) = query_leader.get(skeleton.leader) else {
+            continue;
+        };
+        let up = leader.snake_head.bodies[0].up;
+        let bones = resample_bones(&chain_points(leader), skeleton.joints.len(), up);
+        // the bones are in world space, the root joint is placed in the model
+        let root = skeleton
+            .joints
+            .first()
+            .and_then(|&joint| query_parent.get(joint).ok())
+            .and_then(|parent| query_global.get(parent.get()).ok())
+            .map_or(Affine3A::IDENTITY, |global| global.affine());
+        let mut parent = root;
+        for (&joint, bone) in skeleton.joints.iter().zip(bones) {
+            if let Ok(mut tm) = query_tm.get_mut(joint) {
+                let bone = bone.compute_affine();
+                *tm = Transform::from_matrix((parent.inverse() * bone).into());
+                if skeleton.chained {
+                    parent = bone;
+                }
+            }
+        }
+    }
+}
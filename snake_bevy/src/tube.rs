@@ -0,0 +1,461 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        view::NoFrustumCulling,
+    },
+};
+use std::f32::consts::TAU;
+
+use snake_bevy::logic::*;
+use snake_bevy::skeleton::catmull_rom;
+
+/// Two centerline points farther apart than this are on both sides of a portal.
+const JUMP: f32 = RADIUS * 4.0;
+
+/// Where the centerline of the tube comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum TubeSource {
+    /// A curve through the body positions.
+    #[default]
+    Bodies,
+    /// The recorded head path, from the head back to the tail.
+    Path,
+}
+
+/// Shape of the snake tube. `M` switches between the tube and the body spheres, `N` between
+/// the centerline sources.
+#[derive(Resource)]
+pub struct TubeSettings {
+    pub source: TubeSource,
+    /// Radius at the head.
+    pub radius: f32,
+    /// Radius at the tail relative to the head.
+    pub tail_scale: f32,
+    /// Vertices around the tube.
+    pub sides: usize,
+    /// Rings from one body to the next.
+    pub rings_per_body: usize,
+    /// Close the ends of the tube.
+    pub caps: bool,
+    pub visible: bool,
+}
+
+impl Default for TubeSettings {
+    fn default() -> Self {
+        Self {
+            source: TubeSource::Bodies,
+            radius: RADIUS,
+            tail_scale: 0.4,
+            sides: 16,
+            rings_per_body: 4,
+            caps: true,
+            visible: false,
+        }
+    }
+}
+
+/// Mesh entity of the tube of the leader `0`.
+#[derive(Component)]
+struct Tube(Entity);
+
+#[derive(Default)]
+struct TubeBuffers {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl TubeBuffers {
+    fn clear(&mut self) {
+        self.positions.clear();
+        self.normals.clear();
+        self.uvs.clear();
+        self.indices.clear();
+    }
+
+    fn push(&mut self, p: Vec3, n: Vec3, uv: [f32; 2]) {
+        self.positions.push(p.to_array());
+        self.normals.push(n.to_array());
+        self.uvs.push(uv);
+    }
+}
+
+/// Split `points` where two of them are farther apart than `JUMP`.
+fn split_at_portals(points: &[Vec3]) -> Vec<&[Vec3]> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for i in 1..=points.len() {
+        if i == points.len() || points[i - 1].distance(points[i]) > JUMP {
+            pieces.push(&points[start..i]);
+            start = i;
+        }
+    }
+    pieces
+}
+
+/// A curve through `points` with `rings` points from one to the next, split at portals.
+fn curve_through(points: &[Vec3], rings: usize) -> Vec<Vec<Vec3>> {
+    let mut pieces = Vec::new();
+    for piece in split_at_portals(points) {
+        let mut curve = Vec::new();
+        for i in 0..piece.len().saturating_sub(1) {
+            let p0 = piece[i.saturating_sub(1)];
+            let p3 = piece[(i + 2).min(piece.len() - 1)];
+            for k in 0..rings {
+                let t = k as f32 / rings as f32;
+                curve.push(catmull_rom(p0, piece[i], piece[i + 1], p3, t));
+            }
+        }
+        curve.extend(piece.last());
+        pieces.push(curve);
+    }
+    pieces
+}
+
+/// `points` resampled every `step`, split at portals.
+fn resample(points: &[Vec3], step: f32) -> Vec<Vec<Vec3>> {
+    let mut pieces = Vec::new();
+    for piece in split_at_portals(points) {
+        let mut curve: Vec<Vec3> = piece.first().into_iter().copied().collect();
+        let mut remain = step;
+        for w in piece.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let len = a.distance(b);
+            let mut s = remain;
+            while s <= len {
+                curve.push(a.lerp(b, s / len));
+                s += step;
+            }
+            remain = s - len;
+        }
+        if let Some(&last) = piece.last() {
+            if curve.last() != Some(&last) {
+                curve.push(last);
+            }
+        }
+        pieces.push(curve);
+    }
+    pieces
+}
+
+/// Sweep rings around every piece of the centerline, the radius going from
+/// `settings.radius` at the start of the first piece to `tail_scale` of it at the end of the last.
+fn build_tube(pieces: &[Vec<Vec3>], settings: &TubeSettings, out: &mut TubeBuffers) {
+    let sides = settings.sides.max(3);
+    let total: f32 = pieces
+        .iter()
+        .flat_map(|piece| piece.windows(2).map(|w| w[0].distance(w[1])))
+        .sum();
+    let radius_at = |s: f32| {
+        let k = if total > 0.0 { s / total } else { 0.0 };
+        settings.radius * (1.0 + (settings.tail_scale - 1.0) * k)
+    };
+    let v_scale = 1.0 / (TAU * settings.radius);
+    let mut s = 0.0;
+    for piece in pieces.iter().filter(|piece| piece.len() > 1) {
+        let n = piece.len();
+        let tangent =
+            |i: usize| (piece[(i + 1).min(n - 1)] - piece[i.saturating_sub(1)]).normalize_or_zero();
+        // carry the ring orientation along the curve so it doesn't twist
+        let t0 = tangent(0);
+        let mut normal = (Vec3::Y - t0 * Vec3::Y.dot(t0))
+            .try_normalize()
+            .unwrap_or_else(|| t0.any_orthonormal_vector());
+        let first_ring = out.positions.len() as u32;
+        let mut frames = Vec::with_capacity(n);
+        for i in 0..n {
+            if i > 0 {
+                s += piece[i - 1].distance(piece[i]);
+            }
+            let t = tangent(i);
+            normal = (normal - t * normal.dot(t))
+                .try_normalize()
+                .unwrap_or(normal);
+            let binormal = t.cross(normal);
+            let r = radius_at(s);
+            for j in 0..=sides {
+                let a = j as f32 / sides as f32 * TAU;
+                let dir = normal * a.cos() + binormal * a.sin();
+                out.push(
+                    piece[i] + dir * r,
+                    dir,
+                    [j as f32 / sides as f32, s * v_scale],
+                );
+            }
+            frames.push((t, normal, binormal, r));
+        }
+        let stride = sides as u32 + 1;
+        for i in 0..n as u32 - 1 {
+            for j in 0..sides as u32 {
+                let a = first_ring + i * stride + j;
+                let (b, c, d) = (a + 1, a + stride, a + stride + 1);
+                out.indices.extend([a, b, c, b, d, c]);
+            }
+        }
+        if settings.caps {
+            for (end, outward) in [(0, -1.0), (n - 1, 1.0)] {
+                let (t, normal, binormal, r) = frames[end];
+                let facing = t * outward;
+                let center = out.positions.len() as u32;
+                out.push(piece[end], facing, [0.5, 0.5]);
+                for j in 0..=sides {
+                    let a = j as f32 / sides as f32 * TAU;
+                    let (cos, sin) = (a.cos(), a.sin());
+                    let dir = normal * cos + binormal * sin;
+                    out.push(
+                        piece[end] + dir * r,
+                        facing,
+                        [0.5 + cos * 0.5, 0.5 + sin * 0.5],
+                    );
+                }
+                for j in 0..sides as u32 {
+                    let (v0, v1) = (center + 1 + j, center + 2 + j);
+                    if outward > 0.0 {
+                        out.indices.extend([center, v0, v1]);
+                    } else {
+                        out.indices.extend([center, v1, v0]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Give every new leader a tube mesh.
+fn attach_tube(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query_leader: Query<Entity, Added<Leader>>,
+) {
+    for leader in query_leader.iter() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList)
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_POSITION,
+                VertexAttributeValues::Float32x3(Vec::new()),
+            )
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_NORMAL,
+                VertexAttributeValues::Float32x3(Vec::new()),
+            )
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_UV_0,
+                VertexAttributeValues::Float32x2(Vec::new()),
+            );
+        mesh.set_indices(Some(Indices::U32(Vec::new())));
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(mesh),
+                material: materials.add(StandardMaterial::from(super::color(0))),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            NoFrustumCulling,
+            Tube(leader),
+        ));
+    }
+}
+
+fn update_tube(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<TubeSettings>,
+    query_leader: Query<&Leader>,
+    mut query_tube: Query<(&Tube, &Handle<Mesh>, &mut Visibility)>,
+    mut query_body: Query<(&Transform, &mut Visibility), Without<Tube>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut buffers: Local<TubeBuffers>,
+) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        settings.visible = !settings.visible;
+    }
+    if keyboard_input.just_pressed(KeyCode::N) {
+        settings.source = match settings.source {
+            TubeSource::Bodies => TubeSource::Path,
+            TubeSource::Path => TubeSource::Bodies,
+        };
+    }
+    let (body_visibility, tube_visibility) = if settings.visible {
+        (Visibility::Hidden, Visibility::Inherited)
+    } else {
+        (Visibility::Inherited, Visibility::Hidden)
+    };
+    for (tube, mesh, mut visibility) in query_tube.iter_mut() {
+        let Ok(leader) = query_leader.get(tube.0) else {
+            continue;
+        };
+        *visibility = tube_visibility;
+        let mut points = Vec::with_capacity(leader.followers.len() + 1);
+        let mut iter_body =
+            query_body.iter_many_mut(std::iter::once(&tube.0).chain(&leader.followers));
+        while let Some((tm, mut visibility)) = iter_body.fetch_next() {
+            *visibility = body_visibility;
+            points.push(tm.translation);
+        }
+        if !settings.visible {
+            continue;
+        }
+        let pieces = match settings.source {
+            TubeSource::Bodies => curve_through(&points, settings.rings_per_body.max(1)),
+            TubeSource::Path => {
+                let snake_head = &leader.snake_head;
+                let tail = snake_head.bodies.last().unwrap().move_distance();
+                let mut path: Vec<_> = snake_head
+                    .get_path_records()
+                    .filter(|rec| rec.1 >= tail)
+                    .map(|rec| rec.2)
+                    .collect();
+                path.reverse();
+                resample(&path, DISTANCE / settings.rings_per_body.max(1) as f32)
+            }
+        };
+        buffers.clear();
+        build_tube(&pieces, &settings, &mut buffers);
+
+        let Some(mesh) = meshes.get_mut(mesh) else {
+            continue;
+        };
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            positions.clone_from(&buffers.positions);
+        }
+        if let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+        {
+            normals.clone_from(&buffers.normals);
+        }
+        if let Some(VertexAttributeValues::Float32x2(uvs)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0)
+        {
+            uvs.clone_from(&buffers.uvs);
+        }
+        if let Some(Indices::U32(indices)) = mesh.indices_mut() {
+            indices.clone_from(&buffers.indices);
+        }
+    }
+}
+
+pub struct TubePlugin;
+
+impl Plugin for TubePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TubeSettings>()
+            .add_systems(PostUpdate, (attach_tube, update_tube).chain());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_passes_through_the_points() {
+        let points = [
+            Vec3::ZERO,
+            Vec3::new(DISTANCE, 0.0, 0.0),
+            Vec3::new(DISTANCE, 0.0, DISTANCE),
+        ];
+        let pieces = curve_through(&points, 4);
+        assert_eq!(pieces.len(), 1);
+        let curve = &pieces[0];
+        assert_eq!(curve.len(), 2 * 4 + 1);
+        for (i, p) in points.iter().enumerate() {
+            assert!(curve[i * 4].distance(*p) < 1e-4, "{} {}", curve[i * 4], p);
+        }
+    }
+
+    #[test]
+    fn curve_splits_at_portals() {
+        let points = [
+            Vec3::ZERO,
+            Vec3::new(DISTANCE, 0.0, 0.0),
+            Vec3::new(1000.0, 0.0, 0.0),
+            Vec3::new(1000.0 + DISTANCE, 0.0, 0.0),
+            Vec3::new(1000.0 + DISTANCE * 2.0, 0.0, 0.0),
+        ];
+        let pieces = curve_through(&points, 2);
+        let lens: Vec<_> = pieces.iter().map(Vec::len).collect();
+        assert_eq!(lens, [3, 5]);
+        let pieces = resample(&points, DISTANCE);
+        let lens: Vec<_> = pieces.iter().map(Vec::len).collect();
+        assert_eq!(lens, [2, 3]);
+    }
+
+    #[test]
+    fn resample_keeps_the_step_and_the_ends() {
+        let points = [
+            Vec3::ZERO,
+            Vec3::new(10.0, 0.0, 0.0),
+            Vec3::new(10.0, 0.0, 15.0),
+        ];
+        let pieces = resample(&points, 4.0);
+        assert_eq!(pieces.len(), 1);
+        let curve = &pieces[0];
+        assert_eq!(curve.first(), Some(&Vec3::ZERO));
+        assert_eq!(curve.last(), Some(&Vec3::new(10.0, 0.0, 15.0)));
+        // 25 long, a point every 4 and the end
+        assert_eq!(curve.len(), 8);
+        for w in curve[..curve.len() - 1].windows(2) {
+            // corners cut across, straight runs keep the step
+            assert!(w[0].distance(w[1]) <= 4.0 + 1e-4);
+        }
+        assert!(curve[1].distance(Vec3::new(4.0, 0.0, 0.0)) < 1e-4);
+        assert!(curve[3].distance(Vec3::new(10.0, 0.0, 2.0)) < 1e-4);
+    }
+
+    #[test]
+    fn tube_rings_and_caps() {
+        let settings = TubeSettings {
+            radius: 10.0,
+            tail_scale: 0.5,
+            sides: 8,
+            caps: false,
+            ..default()
+        };
+        let piece: Vec<_> = (0..5)
+            .map(|i| Vec3::new(i as f32 * 10.0, 0.0, 0.0))
+            .collect();
+        let mut out = TubeBuffers::default();
+        build_tube(std::slice::from_ref(&piece), &settings, &mut out);
+        let stride = settings.sides + 1;
+        assert_eq!(out.positions.len(), 5 * stride);
+        assert_eq!(out.normals.len(), out.positions.len());
+        assert_eq!(out.uvs.len(), out.positions.len());
+        assert_eq!(out.indices.len(), 4 * settings.sides * 6);
+        assert!(out
+            .indices
+            .iter()
+            .all(|&i| (i as usize) < out.positions.len()));
+        // the radius goes from the head radius to the tail scale of it
+        for (ring, r) in [(0, 10.0), (2, 7.5), (4, 5.0)] {
+            for p in &out.positions[ring * stride..(ring + 1) * stride] {
+                let d = Vec3::from_array(*p).distance(piece[ring]);
+                assert!((d - r).abs() < 1e-4, "ring {} at {}", ring, d);
+            }
+        }
+
+        let mut capped = TubeBuffers::default();
+        let settings = TubeSettings {
+            caps: true,
+            ..settings
+        };
+        build_tube(&[piece], &settings, &mut capped);
+        assert_eq!(
+            capped.positions.len(),
+            out.positions.len() + 2 * (stride + 1)
+        );
+        assert_eq!(
+            capped.indices.len(),
+            out.indices.len() + 2 * settings.sides * 3
+        );
+    }
+
+    #[test]
+    fn single_points_build_nothing() {
+        let mut out = TubeBuffers::default();
+        build_tube(&[vec![Vec3::ZERO]], &TubeSettings::default(), &mut out);
+        assert!(out.positions.is_empty() && out.indices.is_empty());
+    }
+}
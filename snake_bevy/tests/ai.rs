@@ -0,0 +1,80 @@
+//! Computer controlled snakes through the flat api.
+
+use bevy::prelude::{Vec3, Vec3Swizzles};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn head(app: &mut snake_bevy::App, snake: u64) -> Vec3 {
+    let mut positions = [0.0; 3 * 32];
+    snake_bevy::get_snake(app, snake, &mut positions);
+    Vec3::from_slice(&positions[..3])
+}
+
+#[test]
+fn ai_follows_waypoints() {
+    let mut app = snake_bevy::init(None);
+    let waypoints = [
+        Vec3::new(1000.0, 30.0, 600.0),
+        Vec3::new(1000.0, 30.0, 1000.0),
+        Vec3::new(600.0, 30.0, 1000.0),
+    ];
+    let flat: Vec<_> = waypoints.iter().flat_map(|w| w.to_array()).collect();
+    let snake = snake_bevy::add_ai_snake(&mut app, &[600.0, 30.0, 600.0], 4, &flat);
+    let mut closest = [f32::MAX; 3];
+    for _ in 0..600 {
+        snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+        let p = head(&mut app, snake);
+        for (c, w) in closest.iter_mut().zip(&waypoints) {
+            *c = c.min(p.distance(*w));
+        }
+    }
+    assert!(closest.iter().all(|&c| c < 60.0), "{:?}", closest);
+}
+
+#[test]
+fn ai_collects_pickups_in_sight() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    let snake = snake_bevy::add_ai_snake(&mut app, &[800.0, 30.0, 800.0], 3, &[]);
+    snake_bevy::add_pickup(&mut app, &[1000.0, 30.0, 900.0]);
+    let mut positions = [0.0; 3 * 32];
+    assert_eq!(snake_bevy::get_snake(&mut app, snake, &mut positions), 3);
+    for _ in 0..300 {
+        snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+    }
+    assert_eq!(snake_bevy::get_snake(&mut app, snake, &mut positions), 4);
+    // the player snake is still the one of the flat api
+    assert_eq!(snake_bevy::body_count(&mut app), 10);
+}
+
+#[test]
+fn formation_commands_are_for_the_player() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    let snake = snake_bevy::add_ai_snake(&mut app, &[800.0, 30.0, 800.0], 4, &[]);
+    snake_bevy::stack(&mut app, true);
+    snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+    assert!(snake_bevy::is_stacking(&mut app));
+    assert!(!snake_bevy::is_snake_stacking(&mut app, snake));
+}
+
+#[test]
+fn ai_flees_from_a_head() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    // the player head stays at the origin, a wandering snake would walk slowly
+    let start = Vec3::new(120.0, 30.0, 0.0);
+    let snake = snake_bevy::add_ai_snake(&mut app, &start.to_array(), 3, &[]);
+    let mut p = start;
+    let mut top_speed = 0.0f32;
+    // before it reaches the portal it runs to
+    for _ in 0..36 {
+        snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+        let next = head(&mut app, snake);
+        top_speed = top_speed.max(next.distance(p) / DT);
+        p = next;
+        assert!(p.xz().length() > 100.0, "{}", p);
+    }
+    assert!(p.xz().length() > start.xz().length() + 30.0, "{}", p);
+    assert!(top_speed > 300.0, "{}", top_speed);
+}
@@ -0,0 +1,29 @@
+//! Axis input relative to the camera through the flat api.
+
+use bevy::prelude::{Quat, Vec3};
+use std::f32::consts::FRAC_PI_2;
+
+fn walk(rotation: Option<Quat>, axis: [f32; 2]) -> Vec3 {
+    let mut app = snake_bevy::init(None);
+    let mut positions = [0.0; 30];
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut positions);
+    let start = Vec3::from_slice(&positions[..3]);
+    snake_bevy::set_camera_rotation(&mut app, &rotation.map_or([0.0; 4], |q| q.to_array()));
+    for _ in 0..30 {
+        snake_bevy::update(&mut app, 1.0 / 60.0, &[0.0; 6], &axis, &mut positions);
+    }
+    (Vec3::from_slice(&positions[..3]) - start).normalize()
+}
+
+#[test]
+fn axis_follows_camera_yaw() {
+    // without a camera up walks along -z
+    assert!(walk(None, [0.0, 1.0]).distance(Vec3::NEG_Z) < 1e-3);
+    // a camera turned left looks along -x
+    let left = Quat::from_rotation_y(FRAC_PI_2);
+    assert!(walk(Some(left), [0.0, 1.0]).distance(Vec3::NEG_X) < 1e-3);
+    assert!(walk(Some(left), [1.0, 0.0]).distance(Vec3::NEG_Z) < 1e-3);
+    // looking down from above, the top of the view is forward
+    let down = left * Quat::from_rotation_x(-FRAC_PI_2);
+    assert!(walk(Some(down), [0.0, 1.0]).distance(Vec3::NEG_X) < 1e-3);
+}
@@ -0,0 +1,47 @@
+//! Stacking through the flat api.
+
+use bevy::ecs::system::SystemState;
+use bevy::prelude::{Commands, Vec3};
+use snake_bevy::formation::FormationCommand;
+use snake_bevy::logic::{spawn_snake, MovementInput};
+
+const DT: f32 = 1.0 / 60.0;
+
+#[test]
+fn stack_takes_the_set_duration() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    for bad in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        assert!(snake_bevy::set_stack_duration(&mut app, bad).is_err());
+    }
+    snake_bevy::set_stack_duration(&mut app, 0.5).unwrap();
+    snake_bevy::stack(&mut app, true);
+    for _ in 0..15 {
+        snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+    }
+    assert!(snake_bevy::is_stacking(&mut app));
+    for _ in 0..20 {
+        snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+    }
+    assert!(!snake_bevy::is_stacking(&mut app));
+}
+
+#[test]
+fn input_formation_goes_to_the_player_only() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    // a second snake without `SnakeInput` or `SnakeAi`, like one spawned by a game
+    let mut state = SystemState::<Commands>::new(&mut app.world);
+    let other = spawn_snake(
+        &mut state.get_mut(&mut app.world),
+        Vec3::new(0.0, 30.0, 500.0),
+        6,
+        None,
+    );
+    state.apply(&mut app.world);
+    snake_bevy::set_stack_duration(&mut app, 0.5).unwrap();
+    app.world.resource_mut::<MovementInput>().formation = Some(FormationCommand::StackUp(0));
+    snake_bevy::update(&mut app, DT, &[0.0; 6], &[0.0; 2], &mut []);
+    assert!(snake_bevy::is_stacking(&mut app));
+    assert!(!snake_bevy::is_snake_stacking(&mut app, other.to_bits()));
+}
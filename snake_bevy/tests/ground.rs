@@ -0,0 +1,155 @@
+//! Ground layers of overlapping objects, passing under and over a bridge.
+
+use bevy::prelude::{Transform, Vec3};
+use snake_bevy::bench::{move_on_ground, Ground, GroundCollider, GroundMesh};
+
+const RADIUS: f32 = 30.0;
+
+fn two_levels() -> GroundMesh {
+    let obj = include_str!("fixtures/two_levels.obj");
+    GroundMesh::from_obj(obj).unwrap()
+}
+
+/// Walk from `from` to `to` in steps of 10, every position must stay on `layer` at the
+/// height of `from`.
+fn walk(mesh: &GroundMesh, from: Vec3, to: Vec3, layer: u32) {
+    let steps = (from.distance(to) / 10.0) as usize;
+    let mut p = (from, layer);
+    for i in 1..=steps {
+        let next = from.lerp(to, i as f32 / steps as f32);
+        p = move_on_ground(mesh, p.0, next, p.1);
+        assert_eq!(p.1, layer, "{}", p.0);
+        assert!((p.0.y - from.y).abs() < 1e-3, "{}", p.0);
+    }
+    assert!(p.0.distance(to) < 1e-3, "{}", p.0);
+}
+
+#[test]
+fn fix_position_keeps_the_layer_it_stands_on() {
+    let mesh = two_levels();
+    let (p, layer) = mesh.fix_position(Vec3::new(0.0, RADIUS + 2.0, 0.0), 3.0, RADIUS, 0);
+    assert_eq!((p, layer), (Vec3::new(0.0, RADIUS, 0.0), 0));
+    let (p, layer) = mesh.fix_position(Vec3::new(0.0, RADIUS + 102.0, 0.0), 3.0, RADIUS, 1);
+    assert_eq!((p, layer), (Vec3::new(0.0, RADIUS + 100.0, 0.0), 1));
+    // the layer passed in is kept in the air between the floor and the bridge
+    let (_, layer) = mesh.fix_position(Vec3::new(0.0, RADIUS + 50.0, 0.0), 3.0, RADIUS, 1);
+    assert_eq!(layer, 1);
+}
+
+#[test]
+fn fix_position_prefers_the_own_layer() {
+    let floor = GroundMesh::from_obj(PLATFORM).unwrap();
+    let platform = GroundCollider::new(GroundMesh::from_obj(PLATFORM).unwrap(), 1);
+    let tm = Transform::from_xyz(0.0, 2.0, 0.0);
+    let ground = Ground::new(Some(&floor), std::iter::once((&platform, &tm))).unwrap();
+    // sunk into the platform that moved up, the floor is nearer but the platform is kept
+    let p = Vec3::new(0.0, RADIUS + 0.5, 0.0);
+    assert_eq!(
+        ground.fix_position(p, 3.0, RADIUS, 1),
+        (Vec3::new(0.0, RADIUS + 2.0, 0.0), 1)
+    );
+    // from the floor the higher platform is stepped onto
+    let p = Vec3::new(0.0, RADIUS + 1.5, 0.0);
+    assert_eq!(
+        ground.fix_position(p, 3.0, RADIUS, 0),
+        (Vec3::new(0.0, RADIUS + 2.0, 0.0), 1)
+    );
+}
+
+#[test]
+fn snake_passes_under_and_over_the_bridge() {
+    let mesh = two_levels();
+    walk(
+        &mesh,
+        Vec3::new(-300.0, RADIUS, 0.0),
+        Vec3::new(300.0, RADIUS, 0.0),
+        0,
+    );
+    walk(
+        &mesh,
+        Vec3::new(0.0, RADIUS + 100.0, -300.0),
+        Vec3::new(0.0, RADIUS + 100.0, 300.0),
+        1,
+    );
+}
+
+const PLATFORM: &str =
+    "v -1000 0 -1000\nv 1000 0 -1000\nv 1000 0 1000\nv -1000 0 1000\nf 1 2 3\nf 1 3 4\n";
+
+#[test]
+fn snake_rides_a_moving_platform() {
+    let mut app = snake_bevy::init(None);
+    let platform = snake_bevy::add_ground(&mut app, PLATFORM, 1).unwrap();
+    // a layer can only be carried by one ground
+    assert_eq!(snake_bevy::add_ground(&mut app, PLATFORM, 1), None);
+    let mut positions = [0.0; 30];
+    // step onto the platform and let the followers come to a stop
+    for i in 0..70 {
+        let axis = if i < 10 { [1.0, 0.0] } else { [0.0, 0.0] };
+        snake_bevy::update(&mut app, 1.0 / 60.0, &[0.0; 6], &axis, &mut positions);
+    }
+    let before = positions;
+    for i in 1..=30 {
+        let z = i as f32 * 10.0;
+        snake_bevy::set_ground_transform(&mut app, platform, &[0.0, 0.0, z, 0.0, 0.0, 0.0, 1.0]);
+        snake_bevy::update(&mut app, 1.0 / 60.0, &[0.0; 6], &[0.0, 0.0], &mut positions);
+    }
+    for (p, b) in positions.chunks(3).zip(before.chunks(3)) {
+        let moved = Vec3::from_slice(p) - Vec3::from_slice(b);
+        assert!(
+            moved.distance(Vec3::new(0.0, 0.0, 300.0)) < 1.0,
+            "{}",
+            moved
+        );
+    }
+}
+
+#[test]
+fn ground_layers_are_not_shared() {
+    let obj = include_str!("fixtures/two_levels.obj");
+    let mut app = snake_bevy::init(Some(obj));
+    assert_eq!(snake_bevy::add_ground(&mut app, PLATFORM, 0), None);
+    assert_eq!(snake_bevy::add_ground(&mut app, PLATFORM, 1), None);
+    assert!(snake_bevy::add_ground(&mut app, PLATFORM, 2).is_some());
+}
+
+/// Walk the player snake from `spawn` along `axis` over `ramp_bridge.obj`, calling `check`
+/// with the positions of every body after each frame.
+fn walk_snake(spawn: Vec3, axis: [f32; 2], mut check: impl FnMut(&[Vec3])) {
+    let obj = include_str!("fixtures/ramp_bridge.obj");
+    let mut app = snake_bevy::init(Some(obj));
+    let level = format!("spawn {} {} {}", spawn.x, spawn.y, spawn.z);
+    snake_bevy::load_level(&mut app, &level).unwrap();
+    let mut positions = [0.0; 30];
+    for _ in 0..300 {
+        snake_bevy::update(&mut app, 1.0 / 60.0, &[0.0; 6], &axis, &mut positions);
+        let bodies: Vec<_> = positions.chunks(3).map(Vec3::from_slice).collect();
+        check(&bodies);
+    }
+}
+
+#[test]
+fn followers_stay_on_the_bridge() {
+    let mut most_on_bridge = 0;
+    walk_snake(Vec3::new(0.0, RADIUS, -800.0), [0.0, -1.0], |bodies| {
+        let on_bridge = bodies
+            .iter()
+            .filter(|p| p.x.abs() < 90.0 && p.z.abs() < 190.0)
+            .inspect(|p| assert!((p.y - RADIUS - 100.0).abs() < 1.0, "{}", p))
+            .count();
+        most_on_bridge = most_on_bridge.max(on_bridge);
+    });
+    assert!(most_on_bridge >= 4, "{}", most_on_bridge);
+}
+
+#[test]
+fn followers_pass_under_the_bridge() {
+    let mut passed = 0;
+    walk_snake(Vec3::new(-300.0, RADIUS, 0.0), [1.0, 0.0], |bodies| {
+        for p in bodies {
+            assert!((p.y - RADIUS).abs() < 1e-3, "{}", p);
+        }
+        passed = bodies.iter().filter(|p| p.x > 100.0).count();
+    });
+    assert_eq!(passed, 10);
+}
@@ -0,0 +1,37 @@
+//! Level files written by the editor, loaded through the flat api.
+
+use bevy::prelude::Vec3;
+
+#[test]
+fn level_places_snake_portals_and_pickups() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::load_level(
+        &mut app,
+        "# saved by the editor\n\
+         spawn 100 30 50\n\
+         portal 0 30 -200 0 30 200\n\
+         pickup 500 30 0\n\
+         pickup -500 30 0\n",
+    )
+    .unwrap();
+    let mut positions = [0.0; 30];
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut positions);
+
+    assert_eq!(
+        Vec3::from_slice(&positions[..3]),
+        Vec3::new(100.0, 30.0, 50.0)
+    );
+    assert_eq!(
+        &*snake_bevy::get_portals(&mut app),
+        &[0.0, 30.0, -200.0, 0.0, 30.0, 200.0]
+    );
+    let mut pickups: Vec<_> = snake_bevy::get_pickups(&mut app)
+        .chunks(3)
+        .map(|p| p[0])
+        .collect();
+    pickups.sort_by(f32::total_cmp);
+    assert_eq!(pickups, [-500.0, 500.0]);
+
+    let error = snake_bevy::load_level(&mut app, "portal 0 30 0\n").unwrap_err();
+    assert!(error.starts_with("line 1"), "{}", error);
+}
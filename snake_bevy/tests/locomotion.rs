@@ -0,0 +1,102 @@
+//! Head acceleration, turning, pace and body spacing through the flat api.
+
+use bevy::prelude::Vec3;
+use std::f32::consts::PI;
+
+const DT: f32 = 1.0 / 60.0;
+
+/// Head position after every frame of `axis` input.
+fn walk(app: &mut snake_bevy::App, axis: [f32; 2], frames: usize) -> Vec<Vec3> {
+    let mut positions = [0.0; 30];
+    (0..frames)
+        .map(|_| {
+            snake_bevy::update(app, DT, &[0.0; 6], &axis, &mut positions);
+            Vec3::from_slice(&positions[..3])
+        })
+        .collect()
+}
+
+fn app_with_locomotion() -> snake_bevy::App {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut [0.0; 30]);
+    snake_bevy::set_locomotion(&mut app, 600.0, 1200.0, PI, 1.5, 0.5).unwrap();
+    app
+}
+
+#[test]
+fn head_accelerates_and_brakes() {
+    let mut app = app_with_locomotion();
+    let heads = walk(&mut app, [1.0, 0.0], 60);
+    let step = |heads: &[Vec3], i: usize| heads[i].distance(heads[i - 1]) / DT;
+    // 600 per second squared reaches 300 after half a second
+    assert!((step(&heads, 10) - 600.0 * 11.0 * DT).abs() < 1.0);
+    assert!((step(&heads, 59) - 300.0).abs() < 1e-2);
+
+    snake_bevy::set_pace(&mut app, 1);
+    let heads = walk(&mut app, [1.0, 0.0], 60);
+    assert!((step(&heads, 59) - 450.0).abs() < 1e-2);
+
+    snake_bevy::set_pace(&mut app, 0);
+    let heads = walk(&mut app, [0.0, 0.0], 30);
+    // 1200 per second squared stops from 300 in a quarter second
+    assert!(step(&heads, 5) > 0.0);
+    assert!(step(&heads, 29) < 1e-3);
+}
+
+#[test]
+fn head_arcs_into_turns() {
+    let mut app = app_with_locomotion();
+    walk(&mut app, [1.0, 0.0], 60);
+    // turning from +x to -z at half a turn per second takes half a second
+    let heads = walk(&mut app, [0.0, 1.0], 30);
+    let first = heads[1] - heads[0];
+    assert!(first.x > -first.z);
+    let last = (heads[29] - heads[28]).normalize();
+    assert!(last.distance(Vec3::NEG_Z) < 1e-3, "{}", last);
+
+    // without locomotion the head turns at once
+    let mut app = snake_bevy::init(None);
+    walk(&mut app, [1.0, 0.0], 60);
+    let heads = walk(&mut app, [0.0, 1.0], 3);
+    assert!((heads[2] - heads[1]).normalize().distance(Vec3::NEG_Z) < 1e-3);
+}
+
+#[test]
+fn spacing_rejects_bad_values() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    assert!(snake_bevy::set_spacing(&mut app, -0.5, 1.0, 20.0).is_err());
+    assert!(snake_bevy::set_spacing(&mut app, 0.5, f32::NAN, 20.0).is_err());
+    assert!(snake_bevy::set_spacing(&mut app, 0.5, 1.0, f32::INFINITY).is_err());
+    snake_bevy::set_spacing(&mut app, 0.5, 1.0, 20.0).unwrap();
+}
+
+#[test]
+fn head_stops_at_portals() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut [0.0; 30]);
+    snake_bevy::set_locomotion(&mut app, 600.0, 1200.0, 100.0, 1.5, 0.5).unwrap();
+    // the default level has a portal 200 ahead, its exit next to another portal
+    let heads = walk(&mut app, [0.0, 1.0], 90);
+    let jumps: Vec<_> = (1..heads.len())
+        .filter(|&i| heads[i].distance(heads[i - 1]) > 120.0)
+        .collect();
+    let i = *jumps.last().unwrap();
+    assert!(heads[i - 1].distance(heads[i - 2]) / DT > 290.0);
+    // speeding up from 0 again after the last portal
+    assert!(heads[i + 1].distance(heads[i]) / DT <= 600.0 * DT + 1e-3);
+    assert!(heads[i + 5].distance(heads[i + 4]) / DT <= 600.0 * 5.0 * DT + 1e-3);
+}
+
+#[test]
+fn locomotion_rejects_bad_values() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    assert!(snake_bevy::set_locomotion(&mut app, -1.0, 1200.0, PI, 1.5, 0.5).is_err());
+    assert!(snake_bevy::set_locomotion(&mut app, f32::NAN, 1200.0, PI, 1.5, 0.5).is_err());
+    assert!(snake_bevy::set_locomotion(&mut app, 600.0, 0.0, PI, 1.5, 0.5).is_err());
+    assert!(snake_bevy::set_locomotion(&mut app, 600.0, 1200.0, 0.0, 1.5, 0.5).is_err());
+    assert!(snake_bevy::set_locomotion(&mut app, 600.0, 1200.0, PI, 1.5, -0.5).is_err());
+    // turned off the rest is not used
+    snake_bevy::set_locomotion(&mut app, 0.0, 0.0, 0.0, 0.0, 0.0).unwrap();
+}
@@ -0,0 +1,145 @@
+//! Movement metrics of the canned scenarios against golden files, within a small numeric
+//! tolerance, and kept within bounds that say what each scenario may do.
+//! After an intended change write the golden files again with
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test -p snake_bevy --test metrics
+//! ```
+
+use snake_bevy::logic::{DISTANCE, RADIUS};
+use std::fs;
+use std::path::Path;
+
+const FRAMES: u32 = 500;
+const DT: f32 = 1.0 / 60.0;
+/// The report rounds to 0.1, values may drift by a step of that plus a little of their size.
+const TOLERANCE: f32 = 0.1;
+const RELATIVE_TOLERANCE: f32 = 0.01;
+
+/// Upper limits on the metrics of every body of a scenario.
+struct Bounds {
+    overlap: f32,
+    deviation_max: f32,
+    deviation_mean: f32,
+    jitter_mean: f32,
+    teleport_delay: f32,
+}
+
+/// Walking along the path without touching.
+const CALM: Bounds = Bounds {
+    overlap: 0.1,
+    deviation_max: 0.1,
+    deviation_mean: 0.1,
+    jitter_mean: 500.0,
+    teleport_delay: 0.0,
+};
+
+/// Whether every value of `report` is within the tolerance of `expected`, the words equal.
+fn matches_golden(report: &str, expected: &str) -> bool {
+    let (lines, expected_lines): (Vec<_>, Vec<_>) =
+        (report.lines().collect(), expected.lines().collect());
+    lines.len() == expected_lines.len()
+        && lines.iter().zip(&expected_lines).all(|(line, expected)| {
+            let (words, expected): (Vec<_>, Vec<_>) =
+                (line.split(' ').collect(), expected.split(' ').collect());
+            words.len() == expected.len()
+                && words.iter().zip(&expected).all(|(a, b)| {
+                    match (a.parse::<f32>(), b.parse::<f32>()) {
+                        (Ok(a), Ok(b)) => (a - b).abs() <= TOLERANCE + b.abs() * RELATIVE_TOLERANCE,
+                        _ => a == b,
+                    }
+                })
+        })
+}
+
+fn check_scenario(name: &str, ground: bool, bounds: Bounds) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let ground = ground.then(|| fs::read_to_string(dir.join("assets/ground.obj")).unwrap());
+    let script =
+        fs::read_to_string(dir.join("scenarios").join(name).with_extension("txt")).unwrap();
+    let mut app = snake_bevy::init(ground.as_deref());
+    snake_bevy::enable_metrics(&mut app);
+    snake_bevy::run_script(&mut app, &script, FRAMES, DT, |_| {}).unwrap();
+    let report = snake_bevy::metrics_report(&mut app);
+
+    let golden = dir.join("tests/golden").join(name).with_extension("txt");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        fs::write(&golden, &report).unwrap();
+    } else {
+        let expected = fs::read_to_string(&golden).unwrap();
+        assert!(
+            matches_golden(&report, &expected),
+            "metrics of {} changed, run with UPDATE_GOLDEN=1 if intended\n{}\nexpected\n{}",
+            name,
+            report,
+            expected
+        );
+    }
+
+    let mut lines = report.lines();
+    let header: Vec<_> = lines.next().unwrap().split(' ').collect();
+    let mut bodies = 0;
+    for line in lines.filter(|l| !l.starts_with("records")) {
+        let values: Vec<f32> = line.split(' ').map(|v| v.parse().unwrap()).collect();
+        let get = |column: &str| values[header.iter().position(|&h| h == column).unwrap()];
+        let check = |column: &str, bound: f32| {
+            assert!(
+                get(column) <= bound,
+                "{} of {} above {}:\n{}",
+                column,
+                name,
+                bound,
+                report
+            );
+        };
+        check("overlap_max", bounds.overlap);
+        check("deviation_max", bounds.deviation_max);
+        check("deviation_mean", bounds.deviation_mean);
+        check("jitter_mean", bounds.jitter_mean);
+        check("stuck_frames", 0.0);
+        check("teleport_delay_max", bounds.teleport_delay);
+        bodies += 1;
+    }
+    assert_eq!(bodies, 10, "{}", report);
+}
+
+#[test]
+fn straight() {
+    check_scenario("straight", false, CALM);
+}
+
+#[test]
+fn u_turn() {
+    check_scenario("u_turn", false, CALM);
+}
+
+#[test]
+fn formations() {
+    // bodies gathering into or leaving a formation cut across the path
+    check_scenario(
+        "formations",
+        false,
+        Bounds {
+            deviation_max: DISTANCE * 1.5,
+            deviation_mean: RADIUS * 0.1,
+            ..CALM
+        },
+    );
+}
+
+#[test]
+fn tour() {
+    // the lap drives the head into its own bodies on hills and through a portal
+    check_scenario(
+        "tour",
+        true,
+        Bounds {
+            overlap: RADIUS * 1.5,
+            deviation_max: DISTANCE * 2.5,
+            deviation_mean: RADIUS * 0.7,
+            jitter_mean: 5000.0,
+            teleport_delay: 4.0,
+        },
+    );
+}
@@ -0,0 +1,46 @@
+//! Growing the snake by collecting pickups through the flat api.
+
+use bevy::prelude::Vec3;
+
+const DT: f32 = 1.0 / 60.0;
+const RADIUS: f32 = 30.0;
+const DISTANCE: f32 = 80.0;
+
+fn bodies(positions: &[f32]) -> Vec<Vec3> {
+    positions.chunks(3).map(Vec3::from_slice).collect()
+}
+
+#[test]
+fn pickup_grows_the_chain_behind_the_tail() {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+    assert!(snake_bevy::get_pickups(&mut app).is_empty());
+    // far enough for the whole snake to be walking on the recorded path
+    snake_bevy::add_pickup(&mut app, &[1200.0, RADIUS, 0.0]);
+
+    let mut positions = vec![0.0; 3 * 11];
+    let mut grown_at = None;
+    for frame in 0..400 {
+        snake_bevy::update(&mut app, DT, &[0.0; 6], &[1.0, 0.0], &mut positions);
+        let count = snake_bevy::body_count(&mut app);
+        if count == 11 && grown_at.is_none() {
+            grown_at = Some(frame);
+            let b = bodies(&positions);
+            // the new body starts a body distance behind the old tail, on the path
+            assert!((b[10].distance(b[9]) - DISTANCE).abs() < 1.0, "{:?}", b);
+            assert!((b[10].y - RADIUS).abs() < 1e-3);
+        }
+        if grown_at.is_some() {
+            let b = bodies(&positions);
+            assert!(b[10].distance(b[9]) > RADIUS * 2.0 - 1.0, "{:?}", b);
+        }
+    }
+    assert!(grown_at.is_some());
+    assert!(snake_bevy::get_pickups(&mut app).is_empty());
+    // walking straight, the new body keeps the same distance as the others
+    let b = bodies(&positions);
+    let gap = b[0].distance(b[1]);
+    for pair in b.windows(2) {
+        assert!((pair[0].distance(pair[1]) - gap).abs() < 1.0, "{:?}", b);
+    }
+}
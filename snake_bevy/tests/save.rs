@@ -0,0 +1,69 @@
+//! Save files of the player snake, including the unversioned `ZUp` ones of older builds.
+#![cfg(feature = "serde")]
+
+use bevy::prelude::Vec3;
+use snake_bevy::save::{read_save, write_save};
+use snake_move::MoveMode;
+
+/// A save of a 4 body snake that walked a quarter circle at height 10, written by the
+/// baseline build with the head path on the xy plane.
+const BASELINE: &str = include_str!("fixtures/baseline_save.json");
+
+#[test]
+fn baseline_save_loads_with_y_up() {
+    let head = read_save(BASELINE).unwrap();
+    let a = 1.5f32;
+    let expected = Vec3::new(100.0 * a.sin(), 10.0, -100.0 * (1.0 - a.cos()));
+    assert!(
+        head.head_position().distance(expected) < 1e-3,
+        "{}",
+        head.head_position()
+    );
+    assert_eq!(head.bodies.len(), 4);
+    for p in head
+        .get_path()
+        .chain(head.bodies.iter().map(|b| b.position))
+    {
+        assert!((p.y - 10.0).abs() < 1e-3, "{}", p);
+        assert!(p.z <= 1e-3, "{}", p);
+    }
+}
+
+#[test]
+fn baseline_save_keeps_following() {
+    let mut head = read_save(BASELINE).unwrap();
+    let start = head.head_position();
+    for i in 1..=30 {
+        let position = start + Vec3::new(0.0, 0.0, -5.0 * i as f32);
+        head.move_head(1.0 / 60.0, position, 0, MoveMode::Normal);
+        head.update_body(10.0);
+    }
+    // 150 along -z at 300 per second, the followers are 0.1 s and 20 farther apart
+    let mut prev = head.head_position();
+    for body in &head.bodies[1..3] {
+        let p = body.target;
+        assert!(
+            (p.y - 10.0).abs() < 1e-3 && (p.x - start.x).abs() < 1e-3,
+            "{}",
+            p
+        );
+        assert!((p.z - prev.z - 50.0).abs() < 1.0, "{} {}", prev, p);
+        prev = p;
+    }
+}
+
+#[test]
+fn save_round_trips() {
+    let head = read_save(BASELINE).unwrap();
+    let saved = write_save(&head);
+    assert!(saved.contains("\"version\":1"), "{}", saved);
+    let loaded = read_save(&saved).unwrap();
+    assert!(head.get_path().eq(loaded.get_path()));
+    assert_eq!(loaded.head_position(), head.head_position());
+}
+
+#[test]
+fn newer_save_is_rejected() {
+    let saved = write_save(&read_save(BASELINE).unwrap()).replace("\"version\":1", "\"version\":2");
+    assert!(read_save(&saved).is_err());
+}
@@ -0,0 +1,143 @@
+//! Bones resampled from the body chain through the flat api.
+
+use bevy::prelude::*;
+use snake_bevy::logic::{Player, RADIUS};
+use snake_bevy::skeleton::SnakeSkeleton;
+
+#[test]
+fn bones_span_the_chain() {
+    let mut app = snake_bevy::init(None);
+    let mut positions = Vec::new();
+    snake_bevy::run_script(
+        &mut app,
+        "0 axis 1 0\n200 axis 0 0\n",
+        300,
+        1.0 / 60.0,
+        |p| positions = p.to_vec(),
+    )
+    .unwrap();
+    const COUNT: usize = 8;
+    let mut bones = [0.0; COUNT * 7];
+    snake_bevy::get_bones(&mut app, COUNT as u32, &mut bones);
+
+    let head = Vec3::from_slice(&positions[..3]);
+    let tail = Vec3::from_slice(&positions[positions.len() - 3..]);
+    let bones: Vec<_> = bones
+        .chunks(7)
+        .map(|b| (Vec3::from_slice(&b[..3]), Quat::from_slice(&b[3..])))
+        .collect();
+    assert!(bones[0].0.distance(head) < 1e-3, "{} {}", bones[0].0, head);
+    assert!(bones[COUNT - 1].0.distance(tail) < 1e-3);
+    let spacing = head.distance(tail) / (COUNT - 1) as f32;
+    for (w, (_, rotation)) in bones.windows(2).zip(&bones) {
+        assert!((w[0].0.distance(w[1].0) - spacing).abs() < 0.1);
+        // bones face the head, walking along x
+        assert!((*rotation * Vec3::NEG_Z).dot(Vec3::X) > 0.999);
+        assert!((*rotation * Vec3::Y).dot(Vec3::Y) > 0.999);
+    }
+}
+
+const JOINTS: usize = 6;
+
+/// World translation and rotation.
+type Pose = (Vec3, Quat);
+
+/// World poses of the joints of a skeleton under a moved and turned
+/// model root, and the bones of `get_bones`, after walking along x.
+fn skeleton_and_bones(chained: bool) -> (Vec<Pose>, Vec<Pose>) {
+    let mut app = snake_bevy::init(None);
+    snake_bevy::run_script(&mut app, "0 axis 1 0\n", 120, 1.0 / 60.0, |_| {}).unwrap();
+    let model = Transform::from_xyz(100.0, 50.0, -30.0).with_rotation(Quat::from_rotation_y(1.0));
+    let root = app.world.spawn((model, GlobalTransform::from(model))).id();
+    let mut parent = root;
+    let mut joints = Vec::new();
+    for _ in 0..JOINTS {
+        let joint = app.world.spawn(Transform::IDENTITY).set_parent(parent).id();
+        joints.push(joint);
+        if chained {
+            parent = joint;
+        }
+    }
+    let leader = app
+        .world
+        .query_filtered::<Entity, With<Player>>()
+        .single(&app.world);
+    app.world.spawn(SnakeSkeleton {
+        leader,
+        joints: joints.clone(),
+        chained,
+    });
+    snake_bevy::update(&mut app, 0.0, &[0.0; 6], &[0.0; 2], &mut []);
+
+    let mut world = model.compute_affine();
+    let skeleton = joints
+        .iter()
+        .map(|&joint| {
+            let local = app.world.get::<Transform>(joint).unwrap().compute_affine();
+            let joint_world = world * local;
+            if chained {
+                world = joint_world;
+            }
+            let (_, rotation, translation) = joint_world.to_scale_rotation_translation();
+            (translation, rotation)
+        })
+        .collect();
+    let mut bones = [0.0; JOINTS * 7];
+    snake_bevy::get_bones(&mut app, JOINTS as u32, &mut bones);
+    let bones = bones
+        .chunks(7)
+        .map(|b| (Vec3::from_slice(&b[..3]), Quat::from_slice(&b[3..])))
+        .collect();
+    (skeleton, bones)
+}
+
+fn assert_on_bones(chained: bool) {
+    let (skeleton, bones) = skeleton_and_bones(chained);
+    for (joint, bone) in skeleton.iter().zip(&bones) {
+        assert!(joint.0.distance(bone.0) < 1e-2, "{} {}", joint.0, bone.0);
+        assert!(joint.1.dot(bone.1).abs() > 0.9999, "{} {}", joint.1, bone.1);
+    }
+}
+
+#[test]
+fn chained_joints_follow_the_bodies_in_a_moved_model() {
+    assert_on_bones(true);
+}
+
+#[test]
+fn sibling_joints_follow_the_bodies_in_a_moved_model() {
+    assert_on_bones(false);
+}
+
+#[test]
+fn bones_leave_out_gathered_bodies() {
+    let mut app = snake_bevy::init(None);
+    let mut positions = Vec::new();
+    snake_bevy::run_script(
+        &mut app,
+        "0 axis 1 0\n60 stack up\n",
+        120,
+        1.0 / 60.0,
+        |p| positions = p.to_vec(),
+    )
+    .unwrap();
+    assert!(!snake_bevy::is_stacking(&mut app));
+    // the head entity is on top of the first follower, which walks in front now
+    let head = Vec3::from_slice(&positions[..3]);
+    let walking_head = Vec3::from_slice(&positions[3..6]);
+    assert!(head.y > RADIUS * 2.0, "{}", head);
+
+    const COUNT: usize = 8;
+    let mut bones = [0.0; COUNT * 7];
+    snake_bevy::get_bones(&mut app, COUNT as u32, &mut bones);
+    let bones: Vec<_> = bones.chunks(7).map(|b| Vec3::from_slice(&b[..3])).collect();
+    assert!(
+        bones[0].distance(walking_head) < 1e-3,
+        "{} {}",
+        bones[0],
+        walking_head
+    );
+    for bone in bones {
+        assert!((bone.y - RADIUS).abs() < 1.0, "{}", bone);
+    }
+}
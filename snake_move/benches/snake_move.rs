@@ -0,0 +1,93 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::{Vec2, Vec3};
+use snake_move::*;
+
+const RADIUS: f32 = 30.0;
+const DISTANCE: f32 = 80.0;
+const SPEED: f32 = 300.0;
+const DT: f32 = 1.0 / 60.0;
+const LENGTHS: [usize; 3] = [10, 50, 200];
+
+type FixPosition = fn(&SnakeBody, Vec3, Vec3) -> (Vec3, u32);
+
+/// Head position of frame `frame` of a winding walk.
+fn head_at(frame: usize) -> Vec3 {
+    let t = frame as f32 * DT;
+    let dir = Vec2::from_angle((t * 0.7).sin() * 2.0);
+    (dir * SPEED * t).extend(RADIUS)
+}
+
+fn step(snake: &mut SnakeHead, frame: usize) {
+    snake.move_head(DT as f64, head_at(frame), 0, MoveMode::Normal);
+    snake.update_body(RADIUS);
+    snake.solve_body(DT * SPEED, DT * SPEED * 0.1, RADIUS, None::<FixPosition>);
+}
+
+/// A snake of `count` bodies that walked long enough for all of them to be on the trail.
+fn walked_snake(count: usize) -> (SnakeHead, usize) {
+    let bodies = (0..count)
+        .map(|i| {
+            SnakeBody::new(
+                i as f32 * 0.1,
+                i as f32 * DISTANCE,
+                Vec3::new(-(i as f32) * DISTANCE, 0.0, RADIUS),
+            )
+        })
+        .collect();
+    let mut snake = SnakeHead::new(bodies);
+    let frames = (count as f32 * DISTANCE / (DT * SPEED)) as usize + 60;
+    for frame in 0..frames {
+        step(&mut snake, frame);
+    }
+    (snake, frames)
+}
+
+fn bench_snake(c: &mut Criterion) {
+    let mut group = c.benchmark_group("move_head");
+    for count in LENGTHS {
+        let (mut snake, mut frame) = walked_snake(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| {
+                frame += 1;
+                snake.move_head(DT as f64, black_box(head_at(frame)), 0, MoveMode::Normal);
+            });
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("update_body");
+    for count in LENGTHS {
+        let (mut snake, frame) = walked_snake(count);
+        snake.move_head(DT as f64, head_at(frame + 1), 0, MoveMode::Normal);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| snake.update_body(black_box(RADIUS)));
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("solve_body");
+    for count in LENGTHS {
+        let (mut snake, frame) = walked_snake(count);
+        snake.move_head(DT as f64, head_at(frame + 1), 0, MoveMode::Normal);
+        snake.update_body(RADIUS);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| snake.solve_body(DT * SPEED, DT * SPEED * 0.1, RADIUS, None::<FixPosition>));
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("frame");
+    for count in LENGTHS {
+        let (mut snake, mut frame) = walked_snake(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| {
+                frame += 1;
+                step(&mut snake, frame);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_snake);
+criterion_main!(benches);
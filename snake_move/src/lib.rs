@@ -1,2 +1,4 @@
 mod snake_move;
+mod space;
 pub use snake_move::*;
+pub use space::*;
@@ -1,24 +1,53 @@
-use glam::{Mat2, Vec2, Vec3, Vec3Swizzles};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use num_traits::{Float, One, Zero};
+
+use super::space::*;
+
 const SOLVE_STEP: i32 = 8;
+/// The oldest record is moved back to zero time and distance once it is past these, so the
+/// values don't grow for the whole session.
+const REBASE_TIME: f64 = 600.0;
+const REBASE_DISTANCE: f64 = 100000.0;
+/// Records within this distance of the head are kept as they are, moving back looks at them.
+const BACK_DISTANCE: f64 = 40.0;
+/// Records kept as they are at most, for a head walking slowly.
+const BACK_RECORDS: usize = 128;
+/// New records needed before the trail is decimated again.
+const DECIMATE_BATCH: usize = 64;
+/// The records of the last stretch of a decimated trail are kept for decimating again with
+/// the next ones, up to this many.
+const MAX_STRETCH: usize = 256;
 
 fn invert_lerp<T: num_traits::Float>(min: T, max: T, k: T) -> T {
     (k - min) / (max - min)
 }
 
+fn real<S: Space>(v: f64) -> S::Real {
+    S::Real::from_f64(v)
+}
+
+/// `v` of `S` in the axes of `T`, with the same height and place on the walking plane.
+fn convert<S: Space, T: Space<Real = S::Real, Vec2 = S::Vec2>>(v: S::Vec3) -> T::Vec3 {
+    let mut w = T::up_axis() * S::up(v);
+    T::set_plane(&mut w, S::plane(v));
+    w
+}
+
 #[derive(Default, Debug)]
 #[cfg_attr(feature = "serde", derive(Clone, Serialize, Deserialize))]
-struct MoveRecord {
+struct MoveRecord<S: Space> {
     time: f64,
     distance: f64,
-    position: Vec3,
+    position: S::Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    layer: u32,
 }
 
-impl MoveRecord {
-    fn pos2d(&self) -> Vec2 {
-        self.position.xy()
+impl<S: Space> MoveRecord<S> {
+    fn pos2d(&self) -> S::Vec2 {
+        S::plane(self.position)
     }
 }
 
@@ -31,37 +60,186 @@ pub enum MoveMode {
 
 #[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-struct ModeRecord {
+struct ModeRecord<S: Space> {
     distance: f64,
     mode: MoveMode,
-    position: Vec3,
+    position: S::Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    layer: u32,
+}
+
+/// How a body picks its place on the head path from its `delay` and `distance`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FollowMode {
+    /// Where the head was `delay` seconds ago, the body stops that long after the head.
+    Time,
+    /// `distance` behind the head along the path, the body stops with the head.
+    Distance,
+    /// Where the head was `delay * time` seconds ago, then `distance * distance` farther back.
+    Hybrid { time: f32, distance: f32 },
 }
 
+impl Default for FollowMode {
+    fn default() -> Self {
+        FollowMode::Hybrid {
+            time: 1.0,
+            distance: 1.0,
+        }
+    }
+}
+
+/// How many records of the head path are kept.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordLimits {
+    /// Records are dropped while the path, and the distance the head had walked at each
+    /// time, stay within this of the original. 0 keeps every record.
+    pub tolerance: f64,
+    /// Above this, every other old record is dropped.
+    pub capacity: usize,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.05,
+            capacity: 4096,
+        }
+    }
+}
+
+/// Follow distances that shrink when the head slows down and grow when it speeds up, the
+/// snake bunches up when stopping and stretches when sprinting.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpacingModel {
+    /// Scale of the body distances with the head standing still.
+    pub min: f32,
+    /// Scale of the body distances with the head at `speed` or faster.
+    pub max: f32,
+    /// Head speed in distance per second.
+    pub speed: f32,
+    /// Of the critically damped spring pulling the scale to the one of the head speed,
+    /// per second squared.
+    pub stiffness: f32,
+}
+
+impl SpacingModel {
+    fn scale(&self, speed: f64) -> f32 {
+        let k = if self.speed > 0.0 {
+            (speed as f32 / self.speed).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.min + (self.max - self.min) * k
+    }
+}
+
+#[cfg(feature = "serde")]
+fn one() -> f32 {
+    1.0
+}
+
+/// The path of the head and the bodies following it, in the axes and precision of `S`.
 #[cfg_attr(feature = "serde", derive(Clone, Serialize, Deserialize))]
-pub struct SnakeHead {
+pub struct SnakeHead<S: Space = ZUp> {
     time: f64,
     max_distance: f64,
-    move_rec: Vec<MoveRecord>,
-    mode_rec: Vec<ModeRecord>,
-    pub bodies: Vec<SnakeBody>,
+    move_rec: Vec<MoveRecord<S>>,
+    mode_rec: Vec<ModeRecord<S>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    head_move: S::Vec2,
+    /// Seconds of the last head move, for the speeds of the contacts.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    move_time: f64,
+    pub bodies: Vec<SnakeBody<S>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub record_limits: RecordLimits,
+    /// Records up to this distance are decimated already.
+    #[cfg_attr(feature = "serde", serde(default))]
+    decimated_distance: f64,
+    /// Records after `decimated_distance` the last time the trail was decimated.
+    #[cfg_attr(feature = "serde", serde(default))]
+    undecimated: usize,
+    /// Body distances are constant without it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spacing: Option<SpacingModel>,
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    spacing_scale: f32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    spacing_velocity: f32,
 }
 
-impl SnakeHead {
-    pub fn new(bodies: Vec<SnakeBody>) -> Self {
+/// The head touching a body, reported by `solve_body`, `block_head` and `contacts_with`.
+#[derive(Clone, Debug)]
+pub struct SnakeContact<S: Space = ZUp> {
+    /// Index of the body that was hit.
+    pub body: usize,
+    pub point: S::Vec3,
+    /// Closing speed along the contact normal, in distance per second.
+    pub relative_speed: S::Real,
+}
+
+/// Bodies right behind the head, which it never runs into: they follow it closely and
+/// are pulled in front of it by tight turns.
+pub const NECK: usize = 1;
+
+impl<S: Space> SnakeHead<S> {
+    pub fn new(bodies: Vec<SnakeBody<S>>) -> Self {
         assert!(!bodies.is_empty(), "snake bodies is empty");
         Self {
             time: 0.0,
             max_distance: 0.0,
             move_rec: Vec::new(),
             mode_rec: Vec::new(),
+            head_move: S::Vec2::ZERO,
+            move_time: 0.0,
             bodies,
+            record_limits: RecordLimits::default(),
+            decimated_distance: 0.0,
+            undecimated: 0,
+            spacing: None,
+            spacing_scale: 1.0,
+            spacing_velocity: 0.0,
         }
     }
 
-    pub fn head_position(&self) -> Vec3 {
+    pub fn head_position(&self) -> S::Vec3 {
         self.bodies[0].position
     }
 
+    /// Append a body after the tail, it starts `distance` behind the head on the path the
+    /// tail came along, as far back as it is recorded, and follows with the same `FollowMode`.
+    pub fn push_body(&mut self, delay: f32, distance: f32) {
+        let tail = self.bodies.last().unwrap();
+        let gap = ((distance - tail.distance) * self.spacing_scale).max(0.0) as f64;
+        // the path of the tail's segment, bodies don't start on the other side of a portal
+        let oldest = match (self.mode_rec.get(tail.segment), self.move_rec.first()) {
+            (Some(seg), Some(rec)) => seg.distance.max(rec.distance),
+            _ => f64::MAX,
+        };
+        let mut body = SnakeBody::new(delay, distance, tail.position);
+        body.layer = tail.layer;
+        body.move_distance = tail.move_distance;
+        if tail.move_distance > oldest {
+            body.move_distance = (tail.move_distance - gap).max(oldest);
+            body.position = self.path_position(body.move_distance);
+            let p = self
+                .move_rec
+                .partition_point(|rec| rec.distance < body.move_distance);
+            body.layer = self.move_rec[p.min(self.move_rec.len() - 1)].layer;
+        }
+        body.target = body.position;
+        body.target_layer = body.layer;
+        body.follow = tail.follow;
+        body.forward = tail.forward;
+        body.up = tail.up;
+        body.segment = tail.segment;
+        body.position_prev = body.position;
+        self.bodies.push(body);
+    }
+
     pub fn trim_head(&mut self, index: usize) {
         let distance = self.bodies[index].move_distance;
         self.max_distance -= self.move_rec.last().unwrap().distance - distance;
@@ -74,55 +252,67 @@ impl SnakeHead {
             let b = &self.move_rec[p];
             let k = invert_lerp(a.distance, b.distance, distance);
             self.time = a.time + (b.time - a.time) * k;
-            let position = a.position.lerp(b.position, k as f32);
+            let position = a.position.lerp(b.position, real::<S>(k));
+            let layer = if k < 0.5 { a.layer } else { b.layer };
             self.move_rec[p] = MoveRecord {
                 time: self.time,
                 distance,
                 position,
+                layer,
             };
         } else if p == 0 {
             self.max_distance = 0.0;
             let position = self.bodies[index].position;
+            let layer = self.bodies[index].layer;
             self.move_rec.clear();
             self.move_rec.push(MoveRecord {
                 time: self.time,
                 distance,
                 position,
+                layer,
             });
         }
         self.mode_rec.truncate(self.bodies[index].segment + 1);
         if self.mode_rec.is_empty() {
+            let tail = self.bodies.last().unwrap();
             self.mode_rec.push(ModeRecord {
                 distance,
                 mode: MoveMode::Normal,
-                position: self.bodies.last().unwrap().position,
+                position: tail.position,
+                layer: tail.layer,
             });
         }
     }
 
-    pub fn move_head(&mut self, dt: f64, position: Vec3, move_mode: MoveMode) {
+    pub fn move_head(&mut self, dt: f64, position: S::Vec3, layer: u32, move_mode: MoveMode) {
         self.time += dt;
+        if dt > 0.0 {
+            self.move_time = dt;
+        }
+        let prev_distance = self.move_rec.last().map(|rec| rec.distance);
         if !self.move_rec.is_empty() {
-            let pos2d = position.xy();
+            let pos2d = S::plane(position);
             let new_seg = match move_mode {
                 MoveMode::Teleport => true,
                 _ => move_mode != self.mode_rec.last().unwrap().mode,
             };
             if new_seg {
-                let pos1 = match move_mode {
-                    MoveMode::Teleport => position,
-                    _ => self.move_rec.last().unwrap().position,
+                let last_rec = self.move_rec.last().unwrap();
+                let (pos1, layer1) = match move_mode {
+                    MoveMode::Teleport => (position, layer),
+                    _ => (last_rec.position, last_rec.layer),
                 };
                 self.mode_rec.push(ModeRecord {
-                    distance: self.move_rec.last().unwrap().distance,
+                    distance: last_rec.distance,
                     mode: move_mode,
                     position: pos1,
+                    layer: layer1,
                 });
             }
 
             // move back, remove record
-            let back_limit = self.max_distance - 40.0;
-            let mut min_dis = f32::MAX;
+            let back_limit = self.max_distance - BACK_DISTANCE;
+            let mut min_dis = S::Real::max_value();
             let mut index = usize::MAX;
             for i in (0..self.move_rec.len() - 1).rev() {
                 let p = &self.move_rec[i];
@@ -137,7 +327,11 @@ impl SnakeHead {
             }
             if index < self.move_rec.len() {
                 let p = &self.move_rec[index];
-                if p.pos2d().distance(pos2d) as f64 + p.distance < self.max_distance {
+                if p.pos2d().distance(pos2d).as_f64() + p.distance < self.max_distance {
+                    if p.distance < self.decimated_distance {
+                        self.decimated_distance = p.distance;
+                        self.undecimated = 0;
+                    }
                     self.move_rec.truncate(index + 1);
                 }
             }
@@ -146,7 +340,7 @@ impl SnakeHead {
             let last_rec = self.move_rec.last().unwrap();
             let cur_dis = match move_mode {
                 MoveMode::Teleport => last_rec.distance,
-                _ => last_rec.distance + last_rec.pos2d().distance(pos2d) as f64,
+                _ => last_rec.distance + last_rec.pos2d().distance(pos2d).as_f64(),
             };
             self.max_distance = self.max_distance.max(cur_dis);
             if !new_seg
@@ -160,32 +354,66 @@ impl SnakeHead {
                 time: self.time,
                 distance: cur_dis,
                 position,
+                layer,
             });
         } else {
             self.move_rec.push(MoveRecord {
                 time: self.time,
                 distance: 0.0,
                 position,
+                layer,
             });
             self.mode_rec.push(ModeRecord {
                 distance: 0.0,
                 mode: move_mode,
                 position,
+                layer,
             });
         }
+        let head_distance = self.move_rec.last().unwrap().distance;
+        if let Some(prev_distance) = prev_distance.filter(|_| dt > 0.0) {
+            self.relax_spacing(dt, (head_distance - prev_distance).abs() / dt);
+        }
         let body0 = &mut self.bodies[0];
         body0.position = position;
         body0.target = position;
-        body0.move_distance = self.move_rec.last().unwrap().distance;
+        body0.layer = layer;
+        body0.target_layer = layer;
+        body0.move_distance = head_distance;
         body0.segment = self.mode_rec.len() - 1;
     }
 
-    pub fn update_body(&mut self, radius: f32) {
+    /// Move the spacing scale toward the one of the head speed `speed`.
+    fn relax_spacing(&mut self, dt: f64, speed: f64) {
+        let Some(spacing) = &self.spacing else {
+            self.spacing_scale = 1.0;
+            self.spacing_velocity = 0.0;
+            return;
+        };
+        // exact step of the critically damped spring, stable for any `dt` and stiffness
+        let dt = dt as f32;
+        let omega = spacing.stiffness.max(0.0).sqrt();
+        let target = spacing.scale(speed);
+        let x = self.spacing_scale - target;
+        let v = self.spacing_velocity;
+        let c = v + omega * x;
+        let decay = (-omega * dt).exp();
+        self.spacing_scale = target + (x + c * dt) * decay;
+        self.spacing_velocity = (v - omega * c * dt) * decay;
+    }
+
+    /// Scale of the body distances, 1 without a spacing model.
+    pub fn spacing_scale(&self) -> f32 {
+        self.spacing_scale
+    }
+
+    pub fn update_body(&mut self, radius: S::Real) {
         if self.move_rec.is_empty() {
             return;
         }
-        let head_pos = self.head_position().xy();
-        let rr4 = radius * radius * 4.0;
+        let head_pos = S::plane(self.head_position());
+        let head_layer = self.bodies[0].layer;
+        let rr4 = radius * radius * real::<S>(4.0);
         let mut min_distance = f64::MAX;
         let mut min_segment = usize::MAX;
 
@@ -193,7 +421,8 @@ impl SnakeHead {
         for i in 1..bodies.len() {
             let (bodies0, bodies1) = bodies.split_at_mut(i);
             let (body, _) = bodies1.split_first_mut().unwrap();
-            let time = self.time - body.delay as f64;
+            let (delay, spacing) = body.follow_offset();
+            let time = self.time - delay;
             let p = self.move_rec.partition_point(|rec| rec.time < time);
             let mut distance = if p > 0 && p < self.move_rec.len() {
                 let a = &self.move_rec[p - 1];
@@ -204,7 +433,9 @@ impl SnakeHead {
             } else {
                 self.move_rec.last().unwrap().distance
             };
-            distance = body.move_distance.max(distance - body.distance as f64);
+            distance = body
+                .move_distance
+                .max(distance - spacing * self.spacing_scale as f64);
             body.move_distance = distance;
             min_distance = min_distance.min(distance);
             let iseg = body.segment;
@@ -219,14 +450,17 @@ impl SnakeHead {
                             body.segment += 1;
                         }
                         MoveMode::Teleport => {
-                            let pos = self.mode_rec[iseg + 1].position;
-                            let pos2d = pos.xy();
-                            if pos2d.distance_squared(head_pos) >= rr4
-                                && bodies0
-                                    .iter()
-                                    .all(|body| pos2d.distance_squared(body.position.xy()) >= rr4)
+                            let rec = &self.mode_rec[iseg + 1];
+                            let pos2d = S::plane(rec.position);
+                            let is_free = |other: &SnakeBody<S>| {
+                                other.layer != rec.layer
+                                    || pos2d.distance_squared(other.pos2d()) >= rr4
+                            };
+                            if (rec.layer != head_layer || pos2d.distance_squared(head_pos) >= rr4)
+                                && bodies0.iter().all(is_free)
                             {
-                                body.position = pos;
+                                body.position = rec.position;
+                                body.layer = rec.layer;
                                 body.segment += 1;
                             }
                         }
@@ -236,39 +470,43 @@ impl SnakeHead {
             }
             match self.mode_rec[iseg].mode {
                 MoveMode::Normal => {
-                    body.target = if distance > self.mode_rec[iseg].distance {
+                    (body.target, body.target_layer) = if distance > self.mode_rec[iseg].distance {
                         let p = self.move_rec.partition_point(|rec| rec.distance < distance);
                         if p > 0 && p < self.move_rec.len() {
                             let a = &self.move_rec[p - 1];
                             let b = &self.move_rec[p];
-                            a.position.lerp(
-                                b.position,
-                                invert_lerp(a.distance, b.distance, distance) as f32,
-                            )
-                        } else if p == 0 {
-                            self.move_rec[0].position
+                            let k = invert_lerp(a.distance, b.distance, distance);
+                            let layer = if k < 0.5 { a.layer } else { b.layer };
+                            (a.position.lerp(b.position, real::<S>(k)), layer)
                         } else {
-                            self.move_rec.last().unwrap().position
+                            let rec = if p == 0 {
+                                &self.move_rec[0]
+                            } else {
+                                self.move_rec.last().unwrap()
+                            };
+                            (rec.position, rec.layer)
                         }
                     } else {
-                        let p0 = self.mode_rec[iseg].position;
-                        let remain = (self.mode_rec[iseg].distance - distance) as f32;
-                        let dis = p0.xy().distance(body.position.xy());
+                        let rec = &self.mode_rec[iseg];
+                        let p0 = rec.position;
+                        let remain = real::<S>(rec.distance - distance);
+                        let dis = S::plane(p0).distance(body.pos2d());
                         if dis > remain {
-                            p0.lerp(body.position, remain / dis)
+                            (p0.lerp(body.position, remain / dis), rec.layer)
                         } else {
-                            body.position
+                            (body.position, body.layer)
                         }
                     };
                 }
                 MoveMode::Teleport => {
                     body.target = body.position;
+                    body.target_layer = body.layer;
                 }
             }
         }
         if min_segment > 0 && min_segment < self.mode_rec.len() {
             self.mode_rec.drain(0..min_segment);
-            for body in bodies.iter_mut().skip(1) {
+            for body in bodies.iter_mut() {
                 body.segment -= min_segment;
             }
         }
@@ -276,6 +514,150 @@ impl SnakeHead {
         if rec_len > 2 && self.move_rec[rec_len / 2 + 1].distance < min_distance {
             self.move_rec.drain(..rec_len / 2);
         }
+        self.decimate();
+        self.limit_records();
+        let first = &self.move_rec[0];
+        if first.time > REBASE_TIME || first.distance > REBASE_DISTANCE {
+            self.rebase();
+        }
+    }
+
+    /// Whether record `i` must be kept to not lose a teleport or a layer change.
+    fn is_break(&self, i: usize) -> bool {
+        let rec = &self.move_rec[i];
+        [i.wrapping_sub(1), i + 1].iter().any(|&j| {
+            self.move_rec.get(j).is_some_and(|other| {
+                other.layer != rec.layer
+                    || (other.distance == rec.distance && other.position != rec.position)
+            })
+        })
+    }
+
+    /// How far record `i` is from what would be interpolated between records `a` and `b`,
+    /// both its position at its distance and its distance at its time.
+    fn record_error(&self, a: usize, b: usize, i: usize) -> f64 {
+        let (ra, rb, rec) = (&self.move_rec[a], &self.move_rec[b], &self.move_rec[i]);
+        let k = if rb.distance > ra.distance {
+            invert_lerp(ra.distance, rb.distance, rec.distance)
+        } else {
+            0.0
+        };
+        let p = ra.position.lerp(rb.position, real::<S>(k));
+        let offset = S::plane(p).distance(rec.pos2d()).as_f64()
+            + (S::up(p) - S::up(rec.position)).abs().as_f64();
+        let k = if rb.time > ra.time {
+            invert_lerp(ra.time, rb.time, rec.time)
+        } else {
+            0.0
+        };
+        let distance = ra.distance + (rb.distance - ra.distance) * k;
+        offset.max((distance - rec.distance).abs())
+    }
+
+    /// Douglas-Peucker on the records older than `BACK_DISTANCE`, run every `DECIMATE_BATCH`
+    /// new records.
+    fn decimate(&mut self) {
+        let tolerance = self.record_limits.tolerance;
+        if tolerance <= 0.0 {
+            return;
+        }
+        let start = self
+            .move_rec
+            .partition_point(|rec| rec.distance < self.decimated_distance);
+        let back_limit = self.max_distance - BACK_DISTANCE;
+        let end = self
+            .move_rec
+            .partition_point(|rec| rec.distance <= back_limit)
+            .max(self.move_rec.len().saturating_sub(BACK_RECORDS));
+        if end < start + self.undecimated + DECIMATE_BATCH {
+            return;
+        }
+        let mut keep = vec![true; self.move_rec.len()];
+        let mut ranges = Vec::new();
+        let mut a = start;
+        for i in start + 1..end {
+            if i == end - 1 || self.is_break(i) {
+                ranges.push((a, i));
+                a = i;
+            }
+        }
+        let mut last = end - 1;
+        while let Some((a, b)) = ranges.pop() {
+            let worst = (a + 1..b)
+                .map(|i| (i, self.record_error(a, b, i)))
+                .max_by(|x, y| x.1.total_cmp(&y.1));
+            match worst {
+                Some((i, error)) if error > tolerance => {
+                    ranges.push((a, i));
+                    ranges.push((i, b));
+                }
+                // the last stretch may go on straight with the next records
+                _ if b == end - 1 && b - a < MAX_STRETCH => last = a,
+                _ => keep[a + 1..b].fill(false),
+            }
+        }
+        self.decimated_distance = self.move_rec[last].distance;
+        self.undecimated = end - 1 - last;
+        let mut keep = keep.into_iter();
+        self.move_rec.retain(|_| keep.next().unwrap());
+    }
+
+    /// Drop every other record of the older half while there are more than
+    /// `record_limits.capacity`, the path gets coarser but stays in place.
+    fn limit_records(&mut self) {
+        while self.move_rec.len() > self.record_limits.capacity.max(16) {
+            let half = self.move_rec.len() / 2;
+            let keep: Vec<_> = (0..self.move_rec.len())
+                .map(|i| i == 0 || i >= half || i % 2 == 0 || self.is_break(i))
+                .collect();
+            if keep.iter().all(|&k| k) {
+                break;
+            }
+            let mut keep = keep.into_iter();
+            self.move_rec.retain(|_| keep.next().unwrap());
+        }
+    }
+
+    /// Number of path records, bounded by `record_limits`.
+    pub fn record_count(&self) -> usize {
+        self.move_rec.len()
+    }
+
+    /// Shift time and distance so the oldest record is at zero, done by `update_body` when
+    /// they get large.
+    pub fn rebase(&mut self) {
+        let Some(first) = self.move_rec.first() else {
+            return;
+        };
+        let (time, distance) = (first.time, first.distance);
+        self.time -= time;
+        self.max_distance -= distance;
+        self.decimated_distance -= distance;
+        for rec in self.move_rec.iter_mut() {
+            rec.time -= time;
+            rec.distance -= distance;
+        }
+        for rec in self.mode_rec.iter_mut() {
+            rec.distance -= distance;
+        }
+        for body in self.bodies.iter_mut() {
+            body.move_distance -= distance;
+        }
+    }
+
+    /// Move the world origin to `origin`, every record and body becomes relative to it.
+    pub fn shift_origin(&mut self, origin: S::Vec3) {
+        for rec in self.move_rec.iter_mut() {
+            rec.position = rec.position - origin;
+        }
+        for rec in self.mode_rec.iter_mut() {
+            rec.position = rec.position - origin;
+        }
+        for body in self.bodies.iter_mut() {
+            body.position = body.position - origin;
+            body.target = body.target - origin;
+            body.position_prev = body.position_prev - origin;
+        }
     }
 
     fn foreach_pair<F: FnMut(usize, usize)>(len: usize, mut f: F) {
@@ -286,30 +668,66 @@ impl SnakeHead {
         }
     }
 
+    /// `head_move` and `body_move` are the moves of the last update, which took `move_time`.
+    fn make_contact(
+        move_time: f64,
+        index: usize,
+        head: &SnakeBody<S>,
+        head_move: S::Vec2,
+        body: &SnakeBody<S>,
+        body_move: S::Vec2,
+        radius: S::Real,
+    ) -> SnakeContact<S> {
+        let n = (body.pos2d() - head.pos2d()).normalize_or_zero();
+        let mut point = head.position;
+        S::set_plane(&mut point, head.pos2d() + n * radius);
+        let closing = (head_move - body_move).dot(n);
+        SnakeContact {
+            body: index,
+            point,
+            relative_speed: if move_time > 0.0 {
+                closing / real::<S>(move_time)
+            } else {
+                S::Real::zero()
+            },
+        }
+    }
+
+    /// Move the bodies toward their targets and push them apart, returns the contacts of
+    /// the head running into the bodies.
     pub fn solve_body<F>(
         &mut self,
-        max_move: f32,
-        min_move: f32,
-        radius: f32,
+        max_move: S::Real,
+        min_move: S::Real,
+        radius: S::Real,
         fix_position: Option<F>,
-    ) where
-        F: Fn(&SnakeBody, Vec3, Vec3) -> Vec3,
+    ) -> Vec<SnakeContact<S>>
+    where
+        F: Fn(&SnakeBody<S>, S::Vec3, S::Vec3) -> (S::Vec3, u32),
     {
-        let rr4 = radius * radius * 4.0;
+        let zero = S::Real::zero();
+        let solve_step = real::<S>(SOLVE_STEP as f64);
+        let rr4 = radius * radius * real::<S>(4.0);
+        let mut contacts: Vec<SnakeContact<S>> = Vec::new();
 
+        let move_time = self.move_time;
         let bodies = &mut self.bodies;
-        bodies[0].max_move = 0.0;
-        bodies[0].delta = Vec2::ZERO;
+        self.head_move = bodies[0].pos2d() - S::plane(bodies[0].position_prev);
+        let head_move = self.head_move;
+        bodies[0].max_move = zero;
+        bodies[0].delta = S::Vec2::ZERO;
         bodies[0].position_prev = bodies[0].position;
         for body in bodies.iter_mut().skip(1) {
             body.max_move = max_move;
-            body.delta = Vec2::ZERO;
+            body.delta = S::Vec2::ZERO;
             body.position_prev = body.position;
-            let target_distance = body.target.xy().distance(body.position.xy());
-            if target_distance > 0.0001 {
-                let k: f32 = invert_lerp(1.5, 4.0, target_distance / radius).clamp(0.0, 1.0);
-                body.max_move = max_move * (1.5 + k * 0.5);
-                body.delta = body.target.xy() - body.position.xy();
+            let target_distance = S::plane(body.target).distance(body.pos2d());
+            if target_distance > real::<S>(0.0001) {
+                let k = invert_lerp(real::<S>(1.5), real::<S>(4.0), target_distance / radius)
+                    .max(zero)
+                    .min(S::Real::one());
+                body.max_move = max_move * (real::<S>(1.5) + k * real::<S>(0.5));
+                body.delta = S::plane(body.target) - body.pos2d();
                 if target_distance > body.max_move {
                     body.delta *= body.max_move / target_distance;
                 }
@@ -318,7 +736,7 @@ impl SnakeHead {
 
         for _ in 0..SOLVE_STEP {
             for body in bodies.iter_mut().skip(1) {
-                body.add_pos2d(body.delta / SOLVE_STEP as f32);
+                body.add_pos2d(body.delta / solve_step);
             }
             Self::foreach_pair(bodies.len(), |i, j| {
                 let body0 = &bodies[i];
@@ -326,25 +744,36 @@ impl SnakeHead {
                 if !(body0.collision && body1.collision) {
                     return;
                 }
-                if (body0.position.z - body1.position.z).abs() > radius * 2.0 {
+                if body0.layer != body1.layer {
                     return;
                 }
                 if body0.pos2d().distance_squared(body1.pos2d()) >= rr4 {
                     return;
                 }
+                if i == 0 && j > NECK && contacts.iter().all(|c| c.body != j) {
+                    contacts.push(Self::make_contact(
+                        move_time,
+                        j,
+                        body0,
+                        head_move,
+                        body1,
+                        body1.delta,
+                        radius,
+                    ));
+                }
                 let v0 = body1.pos2d() - body0.pos2d();
                 let len = v0.length();
-                let d = if len > 0.0001 {
-                    v0 * (radius / len - 0.5)
+                let d = if len > real::<S>(0.0001) {
+                    v0 * (radius / len - real::<S>(0.5))
                 } else {
-                    let (x, y) = ((i * bodies.len() + j) as f32).sin_cos();
-                    radius * Vec2::new(x, y)
+                    let (x, y) = real::<S>((i * bodies.len() + j) as f64).sin_cos();
+                    S::Vec2::new(x, y) * radius
                 };
-                if body0.max_move > 0.0 {
+                if body0.max_move > zero {
                     bodies[i].add_pos2d(-d);
                     bodies[j].add_pos2d(d);
                 } else {
-                    bodies[j].add_pos2d(d * 2.0);
+                    bodies[j].add_pos2d(d * real::<S>(2.0));
                 }
             });
             Self::foreach_pair(bodies.len(), |i, j| {
@@ -353,34 +782,32 @@ impl SnakeHead {
                 if !(body0.collision && body1.collision) {
                     return;
                 }
-                if (body0.position.z - body1.position.z).abs() > radius * 2.0 {
+                if body0.layer != body1.layer {
                     return;
                 }
-                if body0.pos2d().distance_squared(body1.pos2d()) >= rr4 * 1.0001 {
+                if body0.pos2d().distance_squared(body1.pos2d()) >= rr4 * real::<S>(1.0001) {
                     return;
                 }
                 let dp = body1.pos2d() - body0.pos2d();
-                if dp.dot(body1.target.xy() - body0.target.xy()) >= -0.001 {
+                if dp.dot(S::plane(body1.target) - S::plane(body0.target)) >= real::<S>(-0.001) {
                     return;
                 }
-                let vertical = Vec2::new(dp.y, -dp.x);
-                let mut angle = 24.0 * max_move / radius / SOLVE_STEP as f32;
-                let rand_offset = (body0.position + body1.position)
-                    .as_ref()
-                    .iter()
-                    .sum::<f32>()
-                    .sin_cos();
-                if (body0.delta - body1.delta + Vec2::new(rand_offset.0, rand_offset.1))
+                let vertical = S::Vec2::new(dp.y(), -dp.x());
+                let mut angle = real::<S>(24.0) * max_move / radius / solve_step;
+                let sum = body0.position + body1.position;
+                let (p, up) = (S::plane(sum), S::up(sum));
+                let rand_offset = [p.x(), p.y(), up].into_iter().sum::<S::Real>().sin_cos();
+                if (body0.delta - body1.delta + S::Vec2::new(rand_offset.0, rand_offset.1))
                     .dot(vertical)
-                    < 0.0
+                    < zero
                 {
                     angle = -angle;
                 }
-                let offset = Mat2::from_angle(angle).mul_vec2(dp) - dp;
+                let offset = dp.rotate(angle) - dp;
                 // if body0.fix_offset.dot(offset) > 0.0 || body1.fix_offset.dot(offset) < 0.0 {
                 //     return;
                 // }
-                let check_move = |pos: Vec2| {
+                let check_move = |pos: S::Vec2| {
                     for (k, body) in bodies.iter().enumerate() {
                         if k != i && k != j && pos.distance_squared(body.pos2d()) < rr4 {
                             return false;
@@ -388,7 +815,7 @@ impl SnakeHead {
                     }
                     true
                 };
-                if body0.max_move > 0.0 {
+                if body0.max_move > zero {
                     let pos0 = body0.pos2d() - offset;
                     let pos1 = body1.pos2d() + offset;
                     if check_move(pos0) && check_move(pos1) {
@@ -396,76 +823,353 @@ impl SnakeHead {
                         bodies[j].set_pos2d(pos1);
                     }
                 } else {
-                    let pos = body1.pos2d() + 2.0 * offset;
+                    let pos = body1.pos2d() + offset * real::<S>(2.0);
                     if check_move(pos) {
                         bodies[j].set_pos2d(pos);
                     }
                 }
             });
             for body in bodies.iter_mut().skip(1) {
-                let origin = body.position_prev.xy();
+                let origin = S::plane(body.position_prev);
                 let distance = origin.distance(body.pos2d());
                 // body.fix_offset = Vec2::ZERO;
-                if distance >= min_move / SOLVE_STEP as f32 {
+                if distance >= min_move / solve_step {
                     if distance > body.max_move {
                         body.set_pos2d(origin.lerp(body.pos2d(), body.max_move / distance));
                     }
                     if let Some(f) = fix_position.as_ref() {
-                        let fixed = f(body, body.position, body.position_prev);
+                        let (fixed, layer) = f(body, body.position, body.position_prev);
                         // body.fix_offset = fixed.truncate() - body.position.truncate();
                         body.position = fixed;
+                        body.layer = layer;
                     }
                 } else {
                     body.set_pos2d(origin);
                 }
             }
         }
+        contacts
+    }
+
+    /// Position on the head path at `distance`, clamped to the recorded part.
+    fn path_position(&self, distance: f64) -> S::Vec3 {
+        let p = self.move_rec.partition_point(|rec| rec.distance < distance);
+        if p > 0 && p < self.move_rec.len() {
+            let a = &self.move_rec[p - 1];
+            let b = &self.move_rec[p];
+            if b.distance > a.distance {
+                let k = invert_lerp(a.distance, b.distance, distance);
+                a.position.lerp(b.position, real::<S>(k))
+            } else {
+                b.position
+            }
+        } else if p == 0 {
+            self.move_rec[0].position
+        } else {
+            self.move_rec.last().unwrap().position
+        }
+    }
+
+    /// Update `forward` and `up` of every body, after `solve_body`. Forward is the path
+    /// direction around the body, over `radius`, up is the ground normal `normal` returns
+    /// or the up axis of `S`. Where the path runs along the normal, up is carried over from
+    /// the last frame instead, so it doesn't flip.
+    pub fn update_frames<F>(&mut self, radius: S::Real, normal: Option<F>)
+    where
+        F: Fn(&SnakeBody<S>) -> S::Vec3,
+    {
+        if self.move_rec.is_empty() {
+            return;
+        }
+        let h = radius.as_f64() * 0.5;
+        for i in 0..self.bodies.len() {
+            let body = &self.bodies[i];
+            let chord = self.path_position(body.move_distance + h)
+                - self.path_position(body.move_distance - h);
+            let n = normal.as_ref().map_or(S::up_axis(), |f| f(body));
+            let body = &mut self.bodies[i];
+            // a chord longer than the path between its ends goes through a portal
+            let len = chord.length().as_f64();
+            if len > 1e-6 && len < h * 2.0 + 1e-3 {
+                body.forward = chord * real::<S>(1.0 / len);
+            } else if body.forward == S::Vec3::ZERO {
+                S::set_plane(
+                    &mut body.forward,
+                    S::Vec2::new(S::Real::one(), S::Real::zero()),
+                );
+            }
+            let t = body.forward;
+            let transported = (body.up - t * body.up.dot(t)).normalize_or_zero();
+            let projected = n - t * n.dot(t);
+            // how well the normal defines up, 0 along the path
+            let w = projected.length().min(S::Real::one());
+            let up = (projected.normalize_or_zero() * w + transported * (S::Real::one() - w))
+                .normalize_or_zero();
+            if up != S::Vec3::ZERO {
+                body.up = up;
+            } else if transported == S::Vec3::ZERO {
+                body.up = n;
+            }
+        }
+    }
+
+    /// Stop the head moving from `from` to `to` when it would run into one of its bodies,
+    /// returns the blocked position and the contact if any.
+    pub fn block_head(
+        &self,
+        from: S::Vec3,
+        to: S::Vec3,
+        radius: S::Real,
+    ) -> (S::Vec3, Option<SnakeContact<S>>) {
+        let zero = S::Real::zero();
+        let head = &self.bodies[0];
+        let move2d = S::plane(to) - S::plane(from);
+        let mut pos = to;
+        let mut contact = None;
+        for (i, body) in self.bodies.iter().enumerate().skip(1 + NECK) {
+            if !(head.collision && body.collision) || body.layer != head.layer {
+                continue;
+            }
+            let v = S::plane(pos) - body.pos2d();
+            if v.length_squared() >= radius * radius * real::<S>(4.0) || move2d.dot(v) >= zero {
+                continue;
+            }
+            let n = v.try_normalize().unwrap_or(-move2d.normalize_or_zero());
+            S::set_plane(&mut pos, body.pos2d() + n * radius * real::<S>(2.0));
+            if contact.is_none() {
+                contact = Some(Self::make_contact(
+                    self.move_time,
+                    i,
+                    head,
+                    move2d,
+                    body,
+                    S::Vec2::ZERO,
+                    radius,
+                ));
+            }
+        }
+        (pos, contact)
+    }
+
+    /// Contacts of this head running into the bodies of `other`, including its head. The
+    /// head and neck are skipped when `other` is this snake.
+    pub fn contacts_with(&self, other: &SnakeHead<S>, radius: S::Real) -> Vec<SnakeContact<S>> {
+        let head = &self.bodies[0];
+        let skip = if std::ptr::eq(self, other) {
+            1 + NECK
+        } else {
+            0
+        };
+        other
+            .bodies
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .filter(|(_, body)| {
+                head.collision
+                    && body.collision
+                    && body.layer == head.layer
+                    && body.pos2d().distance_squared(head.pos2d())
+                        < radius * radius * real::<S>(4.0)
+            })
+            .map(|(i, body)| {
+                let body_move = body.pos2d() - S::plane(body.position_prev);
+                Self::make_contact(
+                    self.move_time,
+                    i,
+                    head,
+                    self.head_move,
+                    body,
+                    body_move,
+                    radius,
+                )
+            })
+            .collect()
+    }
+
+    /// Apply `f` to every record and body on `layer`, used to carry the snake along with
+    /// a moving ground.
+    pub fn transform_layer<F: Fn(S::Vec3) -> S::Vec3>(&mut self, layer: u32, f: F) {
+        for rec in self.move_rec.iter_mut().filter(|rec| rec.layer == layer) {
+            rec.position = f(rec.position);
+        }
+        for rec in self.mode_rec.iter_mut().filter(|rec| rec.layer == layer) {
+            rec.position = f(rec.position);
+        }
+        for body in self.bodies.iter_mut() {
+            if body.layer == layer {
+                body.position = f(body.position);
+                body.position_prev = f(body.position_prev);
+            }
+            if body.target_layer == layer {
+                body.target = f(body.target);
+            }
+        }
     }
 
-    pub fn get_path(&self) -> impl Iterator<Item = Vec3> + '_ {
+    /// The same snake in the axes of `T`, for loading data saved in another `Space`.
+    pub fn into_space<T: Space<Real = S::Real, Vec2 = S::Vec2>>(self) -> SnakeHead<T> {
+        let position = convert::<S, T>;
+        SnakeHead {
+            time: self.time,
+            max_distance: self.max_distance,
+            move_rec: self
+                .move_rec
+                .into_iter()
+                .map(|rec| MoveRecord {
+                    time: rec.time,
+                    distance: rec.distance,
+                    position: position(rec.position),
+                    layer: rec.layer,
+                })
+                .collect(),
+            mode_rec: self
+                .mode_rec
+                .into_iter()
+                .map(|rec| ModeRecord {
+                    distance: rec.distance,
+                    mode: rec.mode,
+                    position: position(rec.position),
+                    layer: rec.layer,
+                })
+                .collect(),
+            head_move: self.head_move,
+            move_time: self.move_time,
+            bodies: self
+                .bodies
+                .into_iter()
+                .map(|body| SnakeBody {
+                    delay: body.delay,
+                    distance: body.distance,
+                    position: position(body.position),
+                    target: position(body.target),
+                    layer: body.layer,
+                    target_layer: body.target_layer,
+                    collision: body.collision,
+                    follow: body.follow,
+                    forward: position(body.forward),
+                    up: position(body.up),
+                    segment: body.segment,
+                    move_distance: body.move_distance,
+                    delta: body.delta,
+                    max_move: body.max_move,
+                    position_prev: position(body.position_prev),
+                })
+                .collect(),
+            record_limits: self.record_limits,
+            decimated_distance: self.decimated_distance,
+            undecimated: self.undecimated,
+            spacing: self.spacing,
+            spacing_scale: self.spacing_scale,
+            spacing_velocity: self.spacing_velocity,
+        }
+    }
+
+    pub fn get_path(&self) -> impl Iterator<Item = S::Vec3> + '_ {
         self.move_rec.iter().map(|rec| rec.position)
     }
+
+    /// Time, distance and position of every move record, oldest first.
+    pub fn get_path_records(&self) -> impl Iterator<Item = (f64, f64, S::Vec3)> + '_ {
+        self.move_rec
+            .iter()
+            .map(|rec| (rec.time, rec.distance, rec.position))
+    }
+
+    /// Start distance, mode and position of every path segment, oldest first.
+    pub fn get_modes(&self) -> impl Iterator<Item = (f64, MoveMode, S::Vec3)> + '_ {
+        self.mode_rec
+            .iter()
+            .map(|rec| (rec.distance, rec.mode, rec.position))
+    }
+
+    pub fn mode_count(&self) -> usize {
+        self.mode_rec.len()
+    }
 }
 
 #[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct SnakeBody {
+pub struct SnakeBody<S: Space = ZUp> {
     pub delay: f32,
     pub distance: f32,
-    pub position: Vec3,
-    pub target: Vec3,
+    pub position: S::Vec3,
+    pub target: S::Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub layer: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub target_layer: u32,
     pub collision: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub follow: FollowMode,
+    /// Unit direction the body faces and its up, set by `update_frames`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub forward: S::Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub up: S::Vec3,
     segment: usize,
     move_distance: f64,
-    delta: Vec2,
-    max_move: f32,
-    position_prev: Vec3,
+    delta: S::Vec2,
+    max_move: S::Real,
+    position_prev: S::Vec3,
 }
 
-impl SnakeBody {
-    pub fn new(delay: f32, distance: f32, position: Vec3) -> Self {
+impl<S: Space> SnakeBody<S> {
+    pub fn new(delay: f32, distance: f32, position: S::Vec3) -> Self {
         Self {
             delay,
             distance,
             position,
-            target: Vec3::ZERO,
+            target: S::Vec3::ZERO,
+            layer: 0,
+            target_layer: 0,
             collision: true,
+            follow: FollowMode::default(),
+            forward: S::Vec3::ZERO,
+            up: S::up_axis(),
             segment: 0,
             move_distance: f64::MIN,
-            delta: Vec2::ZERO,
-            max_move: 0.0,
-            position_prev: Vec3::ZERO,
+            delta: S::Vec2::ZERO,
+            max_move: S::Real::zero(),
+            position_prev: S::Vec3::ZERO,
         }
     }
-    fn pos2d(&self) -> Vec2 {
-        self.position.xy()
+    /// Index of the path segment the body is on.
+    pub fn segment(&self) -> usize {
+        self.segment
+    }
+    /// Path distance the body has reached.
+    pub fn move_distance(&self) -> f64 {
+        self.move_distance
+    }
+    /// How far the body could move in the last `solve_body`.
+    pub fn max_move(&self) -> S::Real {
+        self.max_move
+    }
+    /// Move toward the target planned by the last `solve_body`, at most `max_move` long.
+    pub fn delta(&self) -> S::Vec2 {
+        self.delta
+    }
+    /// Seconds and distance behind the head, as `follow` picks them.
+    fn follow_offset(&self) -> (f64, f64) {
+        let (time, distance) = match self.follow {
+            FollowMode::Time => (1.0, 0.0),
+            FollowMode::Distance => (0.0, 1.0),
+            FollowMode::Hybrid { time, distance } => (time, distance),
+        };
+        (
+            (self.delay * time) as f64,
+            (self.distance * distance) as f64,
+        )
+    }
+    fn pos2d(&self) -> S::Vec2 {
+        S::plane(self.position)
     }
-    fn set_pos2d(&mut self, p: Vec2) {
-        self.position.x = p.x;
-        self.position.y = p.y;
+    fn set_pos2d(&mut self, p: S::Vec2) {
+        S::set_plane(&mut self.position, p);
     }
-    fn add_pos2d(&mut self, v: Vec2) {
-        self.position.x += v.x;
-        self.position.y += v.y;
+    fn add_pos2d(&mut self, v: S::Vec2) {
+        let p = self.pos2d() + v;
+        S::set_plane(&mut self.position, p);
     }
 }
@@ -0,0 +1,240 @@
+use glam::{DMat2, DVec2, DVec3, Mat2, Vec2, Vec3};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub};
+
+/// Serializable when the `serde` feature is on.
+#[cfg(feature = "serde")]
+pub trait MaybeSerde: serde::Serialize + serde::de::DeserializeOwned {}
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> MaybeSerde for T {}
+#[cfg(not(feature = "serde"))]
+pub trait MaybeSerde {}
+#[cfg(not(feature = "serde"))]
+impl<T> MaybeSerde for T {}
+
+/// Scalar of positions, `f32` or `f64`.
+pub trait Real:
+    num_traits::Float + Debug + Default + Sum + AddAssign + MaybeSerde + 'static
+{
+    fn from_f64(v: f64) -> Self;
+    fn as_f64(self) -> f64;
+}
+
+impl Real for f32 {
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Real for f64 {
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Position on the plane the snake walks on.
+pub trait Vector2:
+    Copy
+    + Debug
+    + Default
+    + PartialEq
+    + MaybeSerde
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Neg<Output = Self>
+    + Mul<Self::Real, Output = Self>
+    + Div<Self::Real, Output = Self>
+    + MulAssign<Self::Real>
+{
+    type Real: Real;
+    const ZERO: Self;
+    fn new(x: Self::Real, y: Self::Real) -> Self;
+    fn x(self) -> Self::Real;
+    fn y(self) -> Self::Real;
+    fn dot(self, rhs: Self) -> Self::Real;
+    fn length(self) -> Self::Real;
+    fn length_squared(self) -> Self::Real;
+    fn distance(self, rhs: Self) -> Self::Real;
+    fn distance_squared(self, rhs: Self) -> Self::Real;
+    fn normalize_or_zero(self) -> Self;
+    fn try_normalize(self) -> Option<Self>;
+    fn lerp(self, rhs: Self, s: Self::Real) -> Self;
+    /// Rotate counterclockwise by `angle` radians.
+    fn rotate(self, angle: Self::Real) -> Self;
+}
+
+pub trait Vector3:
+    Copy
+    + Debug
+    + Default
+    + PartialEq
+    + MaybeSerde
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Neg<Output = Self>
+    + Mul<Self::Real, Output = Self>
+{
+    type Real: Real;
+    const ZERO: Self;
+    fn lerp(self, rhs: Self, s: Self::Real) -> Self;
+    fn dot(self, rhs: Self) -> Self::Real;
+    fn length(self) -> Self::Real;
+    fn normalize_or_zero(self) -> Self;
+}
+
+macro_rules! impl_vector {
+    ($vec2:ty, $vec3:ty, $mat2:ty, $real:ty) => {
+        impl Vector2 for $vec2 {
+            type Real = $real;
+            const ZERO: Self = <$vec2>::ZERO;
+            fn new(x: $real, y: $real) -> Self {
+                <$vec2>::new(x, y)
+            }
+            fn x(self) -> $real {
+                self.x
+            }
+            fn y(self) -> $real {
+                self.y
+            }
+            fn dot(self, rhs: Self) -> $real {
+                <$vec2>::dot(self, rhs)
+            }
+            fn length(self) -> $real {
+                <$vec2>::length(self)
+            }
+            fn length_squared(self) -> $real {
+                <$vec2>::length_squared(self)
+            }
+            fn distance(self, rhs: Self) -> $real {
+                <$vec2>::distance(self, rhs)
+            }
+            fn distance_squared(self, rhs: Self) -> $real {
+                <$vec2>::distance_squared(self, rhs)
+            }
+            fn normalize_or_zero(self) -> Self {
+                <$vec2>::normalize_or_zero(self)
+            }
+            fn try_normalize(self) -> Option<Self> {
+                <$vec2>::try_normalize(self)
+            }
+            fn lerp(self, rhs: Self, s: $real) -> Self {
+                <$vec2>::lerp(self, rhs, s)
+            }
+            fn rotate(self, angle: $real) -> Self {
+                <$mat2>::from_angle(angle).mul_vec2(self)
+            }
+        }
+
+        impl Vector3 for $vec3 {
+            type Real = $real;
+            const ZERO: Self = <$vec3>::ZERO;
+            fn lerp(self, rhs: Self, s: $real) -> Self {
+                <$vec3>::lerp(self, rhs, s)
+            }
+            fn dot(self, rhs: Self) -> $real {
+                <$vec3>::dot(self, rhs)
+            }
+            fn length(self) -> $real {
+                <$vec3>::length(self)
+            }
+            fn normalize_or_zero(self) -> Self {
+                <$vec3>::normalize_or_zero(self)
+            }
+        }
+    };
+}
+
+impl_vector!(Vec2, Vec3, Mat2, f32);
+impl_vector!(DVec2, DVec3, DMat2, f64);
+
+/// Vector types of the positions and which of their axes is up, the snake walks on the
+/// plane of the other two.
+pub trait Space: Clone + Copy + Debug + Default + PartialEq + Send + Sync + 'static {
+    type Real: Real;
+    type Vec2: Vector2<Real = Self::Real>;
+    type Vec3: Vector3<Real = Self::Real>;
+    /// Position on the walking plane.
+    fn plane(v: Self::Vec3) -> Self::Vec2;
+    /// Replace the position on the walking plane, keeping the height.
+    fn set_plane(v: &mut Self::Vec3, p: Self::Vec2);
+    fn up(v: Self::Vec3) -> Self::Real;
+    /// Unit vector pointing up.
+    fn up_axis() -> Self::Vec3;
+}
+
+macro_rules! impl_space {
+    ($(#[$meta:meta])* $name:ident, $vec2:ty, $vec3:ty, $real:ty, z_up) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct $name;
+
+        impl Space for $name {
+            type Real = $real;
+            type Vec2 = $vec2;
+            type Vec3 = $vec3;
+            fn plane(v: $vec3) -> $vec2 {
+                <$vec2>::new(v.x, v.y)
+            }
+            fn set_plane(v: &mut $vec3, p: $vec2) {
+                v.x = p.x;
+                v.y = p.y;
+            }
+            fn up(v: $vec3) -> $real {
+                v.z
+            }
+            fn up_axis() -> $vec3 {
+                <$vec3>::Z
+            }
+        }
+    };
+    ($(#[$meta:meta])* $name:ident, $vec2:ty, $vec3:ty, $real:ty, y_up) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct $name;
+
+        impl Space for $name {
+            type Real = $real;
+            type Vec2 = $vec2;
+            type Vec3 = $vec3;
+            fn plane(v: $vec3) -> $vec2 {
+                <$vec2>::new(v.x, -v.z)
+            }
+            fn set_plane(v: &mut $vec3, p: $vec2) {
+                v.x = p.x;
+                v.z = -p.y;
+            }
+            fn up(v: $vec3) -> $real {
+                v.y
+            }
+            fn up_axis() -> $vec3 {
+                <$vec3>::Y
+            }
+        }
+    };
+}
+
+impl_space!(
+    /// Z up, walking on the xy plane.
+    ZUp, Vec2, Vec3, f32, z_up
+);
+impl_space!(
+    /// Y up, walking on the xz plane, like bevy.
+    YUp, Vec2, Vec3, f32, y_up
+);
+impl_space!(
+    /// `ZUp` with f64 positions for large worlds.
+    ZUp64, DVec2, DVec3, f64, z_up
+);
+impl_space!(
+    /// `YUp` with f64 positions for large worlds.
+    YUp64, DVec2, DVec3, f64, y_up
+);
@@ -0,0 +1,650 @@
+use glam::{DVec3, Vec2, Vec3, Vec3Swizzles};
+use proptest::prelude::*;
+use snake_move::*;
+
+const RADIUS: f32 = 30.0;
+const DISTANCE: f32 = 80.0;
+const SPEED: f32 = 300.0;
+const DT: f32 = 1.0 / 60.0;
+
+type FixPosition = fn(&SnakeBody, Vec3, Vec3) -> (Vec3, u32);
+
+fn new_snake(count: usize) -> SnakeHead {
+    let bodies = (0..count)
+        .map(|i| {
+            SnakeBody::new(
+                i as f32 * 0.1,
+                i as f32 * DISTANCE,
+                Vec3::new(-(i as f32) * DISTANCE, 0.0, RADIUS),
+            )
+        })
+        .collect();
+    SnakeHead::new(bodies)
+}
+
+fn step(snake: &mut SnakeHead, position: Vec3, mode: MoveMode) -> Vec<SnakeContact> {
+    snake.move_head(DT as f64, position, 0, mode);
+    snake.update_body(RADIUS);
+    snake.solve_body(DT * SPEED, DT * SPEED * 0.1, RADIUS, None::<FixPosition>)
+}
+
+/// Move the head toward `to` at `SPEED`, one step per frame.
+fn walk(snake: &mut SnakeHead, to: Vec2, check: &mut impl FnMut(&SnakeHead)) {
+    loop {
+        let pos = snake.head_position();
+        let v = to - pos.xy();
+        let step_len = DT * SPEED;
+        let next = if v.length() > step_len {
+            pos.xy() + v.normalize() * step_len
+        } else {
+            to
+        };
+        step(snake, next.extend(pos.z), MoveMode::Normal);
+        check(snake);
+        if next == to {
+            break;
+        }
+    }
+}
+
+fn wait(snake: &mut SnakeHead, frames: usize, check: &mut impl FnMut(&SnakeHead)) {
+    for _ in 0..frames {
+        let pos = snake.head_position();
+        step(snake, pos, MoveMode::Normal);
+        check(snake);
+    }
+}
+
+fn check_invariants(snake: &SnakeHead) {
+    let records: Vec<_> = snake.get_path_records().collect();
+    assert!(!records.is_empty());
+    for w in records.windows(2) {
+        assert!(w[0].0 <= w[1].0, "time not monotonic: {:?}", w);
+        assert!(w[0].1 <= w[1].1, "distance not monotonic: {:?}", w);
+    }
+    let mode_count = snake.mode_count();
+    assert!(mode_count > 0);
+    for (i, body) in snake.bodies.iter().enumerate() {
+        assert!(
+            body.segment() < mode_count,
+            "body {} segment {} out of {}",
+            i,
+            body.segment(),
+            mode_count
+        );
+        assert!(body.position.is_finite(), "body {} at {}", i, body.position);
+    }
+}
+
+fn max_overlap(snake: &SnakeHead) -> f32 {
+    let mut overlap = 0.0f32;
+    let bodies = &snake.bodies;
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            let d = bodies[i].position.xy().distance(bodies[j].position.xy());
+            overlap = overlap.max(RADIUS * 2.0 - d);
+        }
+    }
+    overlap
+}
+
+fn check_all(snake: &SnakeHead) {
+    check_invariants(snake);
+    let overlap = max_overlap(snake);
+    assert!(overlap < RADIUS * 0.5, "overlap {}", overlap);
+}
+
+/// After waiting, every follower sits on its target.
+fn check_settled(snake: &SnakeHead) {
+    for (i, body) in snake.bodies.iter().enumerate().skip(1) {
+        let d = body.position.xy().distance(body.target.xy());
+        assert!(d < 1.0, "body {} is {} away from its target", i, d);
+    }
+}
+
+#[test]
+fn straight_line() {
+    let mut snake = new_snake(10);
+    walk(&mut snake, Vec2::new(1000.0, 0.0), &mut check_all);
+    wait(&mut snake, 300, &mut check_all);
+    check_settled(&snake);
+    for (i, w) in snake.bodies.windows(2).enumerate() {
+        let d = w[0].position.distance(w[1].position);
+        assert!((d - DISTANCE).abs() < 1.0, "spacing {} at {}", d, i);
+        assert!(w[1].position.y.abs() < 0.01);
+    }
+}
+
+#[test]
+fn u_turn() {
+    let mut snake = new_snake(10);
+    walk(&mut snake, Vec2::new(800.0, 0.0), &mut check_all);
+    walk(&mut snake, Vec2::new(800.0, RADIUS * 3.0), &mut check_all);
+    walk(&mut snake, Vec2::new(0.0, RADIUS * 3.0), &mut check_all);
+    wait(&mut snake, 300, &mut check_all);
+    check_settled(&snake);
+}
+
+#[test]
+fn back_over_own_trail() {
+    let mut snake = new_snake(6);
+    walk(&mut snake, Vec2::new(600.0, 0.0), &mut check_invariants);
+    let max_before = snake.get_path_records().last().unwrap().1;
+    walk(&mut snake, Vec2::new(590.0, 0.0), &mut check_invariants);
+    let max_after = snake.get_path_records().last().unwrap().1;
+    assert!(
+        max_after < max_before,
+        "moving back should remove records: {} {}",
+        max_before,
+        max_after
+    );
+    walk(&mut snake, Vec2::new(900.0, 0.0), &mut check_all);
+    wait(&mut snake, 300, &mut check_all);
+    check_settled(&snake);
+}
+
+#[test]
+fn teleport_chain() {
+    let mut snake = new_snake(8);
+    let exits = [
+        Vec2::new(0.0, 1000.0),
+        Vec2::new(2000.0, 1000.0),
+        Vec2::new(2000.0, -1000.0),
+    ];
+    walk(&mut snake, Vec2::new(400.0, 0.0), &mut check_all);
+    for exit in exits {
+        step(&mut snake, exit.extend(RADIUS), MoveMode::Teleport);
+        check_all(&snake);
+        walk(&mut snake, exit + Vec2::new(800.0, 0.0), &mut check_all);
+    }
+    wait(&mut snake, 600, &mut check_all);
+    check_settled(&snake);
+    let last_exit = exits[exits.len() - 1];
+    for body in snake.bodies.iter() {
+        assert_eq!(body.segment(), snake.bodies[0].segment());
+        assert!((body.position.y - last_exit.y).abs() < 1.0);
+        assert!(body.position.x >= last_exit.x - 1.0);
+    }
+}
+
+#[test]
+fn followers_reach_head_path() {
+    let mut snake = new_snake(10);
+    let corners = [
+        Vec2::new(500.0, 0.0),
+        Vec2::new(500.0, 500.0),
+        Vec2::new(-500.0, 500.0),
+    ];
+    for corner in corners {
+        walk(&mut snake, corner, &mut check_all);
+    }
+    wait(&mut snake, 300, &mut check_all);
+    check_settled(&snake);
+    let path: Vec<_> = snake.get_path().collect();
+    for body in snake.bodies.iter() {
+        let d = path
+            .windows(2)
+            .map(|w| {
+                let (a, b) = (w[0].xy(), w[1].xy());
+                let ab = b - a;
+                let t = ((body.position.xy() - a).dot(ab) / ab.length_squared().max(1e-6))
+                    .clamp(0.0, 1.0);
+                (a + ab * t).distance(body.position.xy())
+            })
+            .fold(f32::MAX, f32::min);
+        assert!(d < 1.0, "body at {} is {} off the path", body.position, d);
+    }
+}
+
+#[test]
+fn head_hits_own_body() {
+    let mut snake = new_snake(10);
+    walk(&mut snake, Vec2::new(600.0, 0.0), &mut check_invariants);
+    walk(&mut snake, Vec2::new(600.0, 150.0), &mut check_invariants);
+    walk(&mut snake, Vec2::new(300.0, 150.0), &mut check_invariants);
+    let mut contacts = Vec::new();
+    for _ in 0..60 {
+        let pos = snake.head_position();
+        contacts.extend(step(
+            &mut snake,
+            pos - Vec3::Y * DT * SPEED,
+            MoveMode::Normal,
+        ));
+    }
+    assert!(!contacts.is_empty());
+    assert!(contacts
+        .iter()
+        .any(|c| c.body > 1 && c.relative_speed > 0.0));
+    // per second, bodies catch up at up to twice the speed of the head
+    assert!(
+        contacts
+            .iter()
+            .all(|c| c.body > NECK && c.relative_speed <= SPEED * 3.0),
+        "{:?}",
+        contacts
+    );
+}
+
+#[test]
+fn neck_never_blocks_the_head() {
+    let mut snake = new_snake(3);
+    snake.bodies[1].position = Vec3::new(-40.0, 0.0, RADIUS);
+    let head = snake.head_position();
+    let to = head - Vec3::X * 10.0;
+    let (pos, contact) = snake.block_head(head, to, RADIUS);
+    assert_eq!(pos, to);
+    assert!(contact.is_none());
+    assert!(snake.contacts_with(&snake, RADIUS).is_empty());
+}
+
+/// Height of the bridge over the floor, layer 1 over layer 0.
+const BRIDGE: f32 = 100.0;
+
+/// Walk a loop that comes back across the own trail along x 300, on a bridge over it when
+/// `bridge`. Every body stands on the layer of its target. Returns the contacts of the head,
+/// how far the bodies on the floor were pushed off the trail at the crossing and the path
+/// distance where the last leg starts.
+fn cross_own_trail(bridge: bool) -> (Vec<SnakeContact>, f32, f64, SnakeHead) {
+    let mut snake = new_snake(20);
+    let fix_position = |body: &SnakeBody, pos: Vec3, _| {
+        let layer = body.target_layer;
+        (pos.xy().extend(RADIUS + BRIDGE * layer as f32), layer)
+    };
+    let mut contacts = Vec::new();
+    let mut pushed = 0.0f32;
+    let mut last_leg = 0.0;
+    let corners = [
+        (Vec2::new(600.0, 0.0), 0),
+        (Vec2::new(600.0, 200.0), 0),
+        (Vec2::new(300.0, 200.0), 0),
+        (Vec2::new(300.0, -300.0), bridge as u32),
+    ];
+    for (to, layer) in corners {
+        last_leg = snake.bodies[0].move_distance();
+        loop {
+            let pos = snake.head_position();
+            let next = pos.xy() + (to - pos.xy()).clamp_length_max(DT * SPEED);
+            let z = RADIUS + BRIDGE * layer as f32;
+            snake.move_head(DT as f64, next.extend(z), layer, MoveMode::Normal);
+            snake.update_body(RADIUS);
+            contacts.extend(snake.solve_body(
+                DT * SPEED,
+                DT * SPEED * 0.1,
+                RADIUS,
+                Some(fix_position),
+            ));
+            check_invariants(&snake);
+            for body in snake.bodies.iter().filter(|b| b.layer == 0) {
+                if (body.position.x - 300.0).abs() < RADIUS * 2.0 && body.position.y < 100.0 {
+                    pushed = pushed.max(body.position.y.abs());
+                }
+            }
+            if next == to {
+                break;
+            }
+        }
+    }
+    (contacts, pushed, last_leg, snake)
+}
+
+#[test]
+fn bodies_pass_over_their_trail_on_a_bridge() {
+    let (contacts, pushed, last_leg, snake) = cross_own_trail(true);
+    assert!(contacts.is_empty(), "{:?}", contacts);
+    assert!(pushed < 1.0, "{}", pushed);
+    // the head and the bodies that followed it onto the bridge stay up there
+    let on_bridge: Vec<_> = snake
+        .bodies
+        .iter()
+        .filter(|b| b.move_distance() > last_leg + DISTANCE as f64)
+        .collect();
+    assert!(on_bridge.len() > 3, "{}", on_bridge.len());
+    for body in on_bridge {
+        assert_eq!(body.layer, 1, "{}", body.position);
+        assert_eq!(body.position.z, RADIUS + BRIDGE);
+    }
+    // the same walk on one layer runs into the trail
+    let (contacts, pushed, _, _) = cross_own_trail(false);
+    assert!(!contacts.is_empty());
+    assert!(pushed > RADIUS, "{}", pushed);
+}
+
+/// Walk to x 600 and stop with every follower in `follow` mode, returns the snake and the
+/// targets of the frame the head stopped.
+fn stop_with(follow: FollowMode) -> (SnakeHead, Vec<Vec3>) {
+    let mut snake = new_snake(5);
+    for body in snake.bodies.iter_mut() {
+        body.follow = follow;
+    }
+    walk(&mut snake, Vec2::new(600.0, 0.0), &mut check_all);
+    let targets = snake.bodies.iter().map(|b| b.target).collect();
+    (snake, targets)
+}
+
+#[test]
+fn time_follow_catches_up_after_stop() {
+    let (mut snake, stopped) = stop_with(FollowMode::Time);
+    wait(&mut snake, 1, &mut check_invariants);
+    assert!(snake.bodies[4].target.x > stopped[4].x);
+    // the last body stops 0.4s after the head, on the head path
+    wait(&mut snake, 30, &mut check_invariants);
+    let head_distance = snake.bodies[0].move_distance();
+    for body in &snake.bodies[1..] {
+        assert!((body.move_distance() - head_distance).abs() < 1e-3);
+        assert!(body.target.distance(snake.head_position()) < 1e-3);
+    }
+}
+
+#[test]
+fn distance_follow_stops_with_head() {
+    let (mut snake, stopped) = stop_with(FollowMode::Distance);
+    wait(&mut snake, 60, &mut check_all);
+    let head = snake.head_position();
+    for (i, body) in snake.bodies.iter().enumerate() {
+        assert_eq!(body.target, stopped[i]);
+        assert!((head.x - body.position.x - i as f32 * DISTANCE).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn hybrid_follow_weights() {
+    let (mut snake, _) = stop_with(FollowMode::Hybrid {
+        time: 2.0,
+        distance: 1.0,
+    });
+    // twice the delay of the default, the last body still walks 0.5s after the head stopped
+    wait(&mut snake, 30, &mut check_all);
+    let target = snake.bodies[4].target;
+    wait(&mut snake, 1, &mut check_all);
+    assert!(snake.bodies[4].target.x > target.x);
+    wait(&mut snake, 60, &mut check_all);
+    check_settled(&snake);
+    let head = snake.head_position();
+    for (i, body) in snake.bodies.iter().enumerate() {
+        assert!((head.x - body.position.x - i as f32 * DISTANCE).abs() < 1e-3);
+    }
+}
+
+fn spacings(snake: &SnakeHead) -> Vec<f32> {
+    snake
+        .bodies
+        .windows(2)
+        .map(|w| w[0].target.distance(w[1].target))
+        .collect()
+}
+
+#[test]
+fn spacing_follows_head_speed() {
+    let mut snake = new_snake(5);
+    for body in snake.bodies.iter_mut() {
+        body.follow = FollowMode::Distance;
+    }
+    snake.spacing = Some(SpacingModel {
+        min: 0.8,
+        max: 1.2,
+        speed: SPEED,
+        stiffness: 20.0,
+    });
+    walk(&mut snake, Vec2::new(2000.0, 0.0), &mut check_all);
+    assert!((snake.spacing_scale() - 1.2).abs() < 1e-3);
+    for d in spacings(&snake) {
+        assert!((d - DISTANCE * 1.2).abs() < 0.1, "sprinting spacing {}", d);
+    }
+    wait(&mut snake, 300, &mut check_all);
+    assert!((snake.spacing_scale() - 0.8).abs() < 1e-3);
+    for d in spacings(&snake) {
+        assert!((d - DISTANCE * 0.8).abs() < 0.1, "stopped spacing {}", d);
+    }
+}
+
+#[test]
+fn stiff_spacing_spring_settles_without_overshoot() {
+    let mut snake = new_snake(3);
+    snake.spacing = Some(SpacingModel {
+        min: 0.5,
+        max: 1.0,
+        speed: SPEED,
+        stiffness: 1e6,
+    });
+    // a long frame with a spring far too stiff for stepping it explicitly
+    snake.move_head(0.5, Vec3::new(0.0, 0.0, RADIUS), 0, MoveMode::Normal);
+    snake.move_head(0.5, Vec3::new(0.0, 0.0, RADIUS), 0, MoveMode::Normal);
+    assert!((snake.spacing_scale() - 0.5).abs() < 1e-3);
+
+    // without a reference speed the spacing is the one at full speed
+    snake.spacing = Some(SpacingModel {
+        speed: 0.0,
+        ..snake.spacing.unwrap()
+    });
+    snake.move_head(0.5, Vec3::new(0.0, 0.0, RADIUS), 0, MoveMode::Normal);
+    assert!(snake.spacing_scale().is_finite());
+    snake.move_head(0.5, Vec3::new(0.0, 0.0, RADIUS), 0, MoveMode::Normal);
+    assert!((snake.spacing_scale() - 1.0).abs() < 1e-3);
+}
+
+fn check_frames(snake: &SnakeHead, normal: Vec3) {
+    for (i, body) in snake.bodies.iter().enumerate() {
+        let (f, u) = (body.forward, body.up);
+        assert!((f.length() - 1.0).abs() < 1e-3, "body {} forward {}", i, f);
+        assert!((u.length() - 1.0).abs() < 1e-3, "body {} up {}", i, u);
+        assert!(f.dot(u).abs() < 1e-3, "body {} forward {} up {}", i, f, u);
+        assert!(u.dot(normal) > 0.9, "body {} up {} normal {}", i, u, normal);
+    }
+}
+
+#[test]
+fn frames_follow_path_and_ground() {
+    let normal = Vec3::new(0.3, 0.0, 1.0).normalize();
+    let mut snake = new_snake(6);
+    let check = |snake: &mut SnakeHead| {
+        snake.update_frames(RADIUS, Some(|_: &SnakeBody| normal));
+        check_frames(snake, normal);
+    };
+    for to in [
+        Vec2::new(400.0, 0.0),
+        Vec2::new(400.0, 400.0),
+        Vec2::new(0.0, 400.0),
+    ] {
+        loop {
+            let pos = snake.head_position();
+            let v = to - pos.xy();
+            let next = pos.xy() + v.clamp_length_max(DT * SPEED);
+            step(&mut snake, next.extend(pos.z), MoveMode::Normal);
+            check(&mut snake);
+            if next == to {
+                break;
+            }
+        }
+    }
+    let head = &snake.bodies[0];
+    assert!(head.forward.dot(Vec3::NEG_X) > 0.95, "{}", head.forward);
+    // the tail is still coming around the last corner
+    let tail = snake.bodies.last().unwrap();
+    assert!(tail.forward.dot(Vec3::Y) > 0.7, "{}", tail.forward);
+}
+
+#[test]
+fn frames_dont_flip_with_normal_along_path() {
+    let mut snake = new_snake(4);
+    walk(&mut snake, Vec2::new(300.0, 0.0), &mut |_| {});
+    snake.update_frames(RADIUS, None::<fn(&SnakeBody) -> Vec3>);
+    check_frames(&snake, Vec3::Z);
+    // the normal turns to the walking direction, up keeps what it had
+    for k in [0.5, 0.9, 1.0] {
+        let normal = Vec3::Z.lerp(Vec3::X, k).normalize();
+        wait(&mut snake, 1, &mut check_invariants);
+        snake.update_frames(RADIUS, Some(|_: &SnakeBody| normal));
+        for body in &snake.bodies {
+            assert!(body.up.dot(Vec3::Z) > 0.99, "{}", body.up);
+            assert!(body.forward.dot(Vec3::X) > 0.99, "{}", body.forward);
+        }
+    }
+}
+
+/// Walk a wave in `S`, `convert` maps z up f32 positions into it.
+fn walk_in<S: Space>(convert: impl Fn(Vec3) -> S::Vec3) -> Vec<S::Vec3> {
+    let real = |v: f32| S::Real::from_f64(v as f64);
+    let bodies = (0..6)
+        .map(|i| {
+            let position = Vec3::new(-(i as f32) * DISTANCE, 0.0, RADIUS);
+            SnakeBody::<S>::new(i as f32 * 0.1, i as f32 * DISTANCE, convert(position))
+        })
+        .collect();
+    let mut snake = SnakeHead::new(bodies);
+    // which records are dropped depends on rounding, compare the full path
+    snake.record_limits.tolerance = 0.0;
+    for frame in 0..600 {
+        let t = frame as f32 * DT;
+        let head = Vec3::new(t * SPEED * 0.5, (t * 2.0).sin() * 100.0, RADIUS);
+        snake.move_head(DT as f64, convert(head), 0, MoveMode::Normal);
+        snake.update_body(real(RADIUS));
+        snake.solve_body(
+            real(DT * SPEED),
+            real(DT * SPEED * 0.1),
+            real(RADIUS),
+            None::<fn(&SnakeBody<S>, S::Vec3, S::Vec3) -> (S::Vec3, u32)>,
+        );
+    }
+    snake.bodies.iter().map(|b| b.position).collect()
+}
+
+#[test]
+fn spaces_agree() {
+    let z_up = walk_in::<ZUp>(|p| p);
+    let y_up = walk_in::<YUp>(|p| Vec3::new(p.x, p.z, -p.y));
+    for (a, b) in z_up.iter().zip(y_up.iter()) {
+        assert!(a.distance(Vec3::new(b.x, -b.z, b.y)) < 1e-3, "{} {}", a, b);
+    }
+    // far from the origin, where f32 positions are only good to a few units
+    let origin = DVec3::new(1e7, -1e7, 0.0);
+    let z_up64 = walk_in::<ZUp64>(|p| p.as_dvec3() + origin);
+    for (a, b) in z_up.iter().zip(z_up64.iter()) {
+        assert!(a.distance((*b - origin).as_vec3()) < 0.01, "{} {}", a, b);
+    }
+}
+
+/// Ten minutes of the head crawling forward while shaking in place, far slower than the
+/// chain spacing is walked.
+fn crawl(snake: &mut SnakeHead, check: &mut impl FnMut(&SnakeHead)) {
+    for frame in 0..36000 {
+        let t = frame as f32 * DT;
+        let head = Vec3::new(t, (t * 30.0).sin() * 0.02, RADIUS);
+        step(snake, head, MoveMode::Normal);
+        if frame % 100 == 0 {
+            check_invariants(snake);
+            check(snake);
+        }
+    }
+    for body in &snake.bodies {
+        assert!(body.position.y.abs() < 0.1, "{}", body.position);
+    }
+}
+
+#[test]
+fn slow_head_records_are_decimated() {
+    let mut snake = new_snake(10);
+    crawl(&mut snake, &mut |snake| {
+        assert!(snake.record_count() < 1000, "{}", snake.record_count())
+    });
+}
+
+#[test]
+fn record_capacity_keeps_path() {
+    let mut snake = new_snake(10);
+    snake.record_limits = RecordLimits {
+        tolerance: 0.0,
+        capacity: 256,
+    };
+    crawl(&mut snake, &mut |snake| {
+        assert!(snake.record_count() <= 256, "{}", snake.record_count())
+    });
+}
+
+/// Hours of walking away from the origin, with the origin following the head.
+#[test]
+fn soak() {
+    const HOURS: f64 = 2.0;
+    const SOAK_DT: f32 = 0.1;
+    let mut snake = new_snake(6);
+    let mut shifted = 0.0f64;
+    let frames = (HOURS * 3600.0 / SOAK_DT as f64) as usize;
+    for frame in 0..frames {
+        let mut head = snake.head_position();
+        head.x += SOAK_DT * SPEED;
+        if head.x > 10000.0 {
+            snake.shift_origin(Vec3::new(head.x, 0.0, 0.0));
+            shifted += head.x as f64;
+            head.x = 0.0;
+        }
+        snake.move_head(SOAK_DT as f64, head, 0, MoveMode::Normal);
+        snake.update_body(RADIUS);
+        snake.solve_body(
+            SOAK_DT * SPEED,
+            SOAK_DT * SPEED * 0.1,
+            RADIUS,
+            None::<FixPosition>,
+        );
+        if frame % 1000 == 0 {
+            check_invariants(&snake);
+            for (time, distance, _) in snake.get_path_records() {
+                assert!(time < 700.0 && distance < 110000.0, "{} {}", time, distance);
+            }
+        }
+    }
+    assert!(shifted > HOURS * 3600.0 * SPEED as f64 * 0.99);
+    wait(&mut snake, 300, &mut check_all);
+    for w in snake.bodies.windows(2) {
+        let d = w[0].position.distance(w[1].position);
+        assert!((d - DISTANCE).abs() < 0.1, "spacing {}", d);
+        assert!(w[1].position.y.abs() < 0.01);
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Input {
+    Move(f32, f32),
+    Stay(u8),
+    Teleport(f32, f32),
+}
+
+fn input_strategy() -> impl Strategy<Value = Input> {
+    prop_oneof![
+        8 => (-1.0f32..1.0, -1.0f32..1.0).prop_map(|(x, y)| Input::Move(x, y)),
+        1 => (1u8..30).prop_map(Input::Stay),
+        1 => (-2000.0f32..2000.0, -2000.0f32..2000.0).prop_map(|(x, y)| Input::Teleport(x, y)),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn random_inputs_keep_invariants(
+        count in 2usize..12,
+        inputs in prop::collection::vec(input_strategy(), 1..200),
+    ) {
+        let mut snake = new_snake(count);
+        for input in inputs {
+            let pos = snake.head_position();
+            match input {
+                Input::Move(x, y) => {
+                    let v = Vec2::new(x, y) * DT * SPEED;
+                    step(&mut snake, pos + v.extend(0.0), MoveMode::Normal);
+                }
+                Input::Stay(frames) => {
+                    for _ in 0..frames {
+                        step(&mut snake, pos, MoveMode::Normal);
+                    }
+                }
+                Input::Teleport(x, y) => {
+                    step(&mut snake, Vec3::new(x, y, pos.z), MoveMode::Teleport);
+                }
+            }
+            check_invariants(&snake);
+            let distances: Vec<_> = snake.bodies.iter().map(|b| b.move_distance()).collect();
+            for w in distances.windows(2).skip(1) {
+                prop_assert!(w[0] >= w[1], "followers out of order: {:?}", distances);
+            }
+        }
+    }
+}